@@ -0,0 +1,665 @@
+use std::{collections::{HashSet, HashMap}, io::{self, Read}, path::Path, fs};
+
+use serde::{Serialize, Deserialize};
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+use crate::{word_filtering::{reader_to_words_with, scan_for_unique_words_with, is_word_char, cjk_bigrams, recognize_structured_token, StructuredToken}, stopwords::StopwordLanguage, stemming::StemmerLanguage, synonyms::Synonyms};
+
+/// Turns text into index terms.
+///
+/// The same analyzer has to be used when inserting documents and when querying,
+/// otherwise query terms will not line up with the indexed ones
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Analyzer {
+    pub tokenizer: Tokenizer,
+    /// Applied to every token in order. A filter may drop a token, in which case the rest are skipped
+    pub filters: Vec<TokenFilter>,
+    /// Expand query words, documents are indexed without them
+    pub synonyms: Synonyms,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tokenizer {
+    pub split_on: SplitOn,
+    pub cjk: CjkMode,
+    pub apostrophes: ApostropheRules,
+    pub hyphens: HyphenMode,
+    /// Kinds of tokens kept whole, like URLs and emails, instead of being split on punctuation
+    pub recognize: Vec<StructuredToken>,
+    /// Also indexes the words a recognized token would have been split into without recognition
+    pub sub_tokens: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitOn {
+    /// Tokens are runs of alphanumeric characters and apostrophes
+    #[default]
+    NonAlphanumeric,
+    /// Tokens are runs of anything but whitespace
+    Whitespace,
+}
+
+/// How runs of Chinese, Japanese and Korean characters are tokenized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CjkMode {
+    /// A whole run is a single token, same as with any other script
+    #[default]
+    Runs,
+    /// A run is split into overlapping character bigrams, so it can be found by any part of it.
+    /// Text in other scripts is tokenized as usual
+    Bigrams,
+}
+
+/// What is done with apostrophes inside tokens. By default tokens keep them as they are
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApostropheRules {
+    /// Treats the typographic apostrophe `’` as `'`
+    pub typographic: bool,
+    /// Strips apostrophes at the start and at the end of tokens, `'Kaalai'` becomes `Kaalai`
+    pub trim: bool,
+    /// Strips a trailing `'s`, `Rajini's` becomes `Rajini`.
+    /// Contractions like `it's` look the same and are stripped as well
+    pub possessives: bool,
+    pub contractions: ContractionMode,
+}
+
+/// What is done with apostrophes left inside a token after trimming and stripping possessives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractionMode {
+    /// `don't` stays `don't`
+    #[default]
+    Keep,
+    /// `don't` gives `don` and `t`
+    Split,
+    /// `don't` becomes `dont`
+    Join,
+}
+
+/// How hyphenated compounds like `life-time` are tokenized
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HyphenMode {
+    /// A hyphen is like any other character, so it splits tokens unless `split_on` is `whitespace`
+    #[default]
+    AsIs,
+    /// `life-time` gives `life` and `time`
+    Parts,
+    /// `life-time` becomes `lifetime`
+    Join,
+    /// `life-time` gives `life`, `time` and `lifetime`, so it is found by any spelling
+    PartsAndJoined,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TokenFilter {
+    Lowercase,
+    /// Stems tokens with the stemmer for `language`.
+    /// With `language: auto`, `fallback` is used for documents whose language could not be detected
+    Stemmer {
+        #[serde(default)]
+        language: StemmerLanguage,
+        #[serde(default)]
+        fallback: StemmerLanguage,
+    },
+    /// Drops tokens shorter than `min` or longer than `max` characters
+    Length { min: usize, max: usize },
+    /// Drops stopwords. Tokens are compared as they are, so it should come after `lowercase`
+    Stopwords(Stopwords),
+    /// Converts tokens into a Unicode normalization form
+    Normalize { form: NormalizationForm },
+    /// Full Unicode case folding. Unlike `lowercase`, also maps `ß` to `ss` and similar
+    CaseFold,
+    /// Strips accents and other combining marks, `café` becomes `cafe`
+    RemoveDiacritics,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizationForm {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+/// What text is being analyzed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Index,
+    Query { keep_stopwords: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "StopwordsConfig", into = "StopwordsConfig")]
+pub struct Stopwords {
+    config: StopwordsConfig,
+    words: HashSet<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StopwordsConfig {
+    /// Built-in lists to include
+    pub languages: Vec<StopwordLanguage>,
+    pub words: Vec<String>,
+    /// Files with one stopword per line. Empty lines and lines starting with `#` are ignored
+    pub files: Vec<String>,
+    pub remove_from: StopwordScope,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopwordScope {
+    /// Stopwords are neither indexed nor looked up
+    #[default]
+    Everywhere,
+    /// Stopwords are indexed, but dropped from queries unless `keep_stopwords` is set for the query
+    Queries,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self {
+            tokenizer: Tokenizer::default(),
+            filters: vec![TokenFilter::Lowercase, TokenFilter::Stemmer {
+                language: StemmerLanguage::Porter,
+                fallback: StemmerLanguage::Porter,
+            }],
+            synonyms: Synonyms::default(),
+        }
+    }
+}
+
+impl Analyzer {
+    /// Reads an analyzer from a YAML or JSON file
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_yaml::from_str(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn tokenize_reader(&self, reader: impl Read) -> io::Result<HashSet<String>> {
+        let words = reader_to_words_with(reader, self.tokenizer.is_token_char())?;
+        Ok(self.tokenizer.split_words(words))
+    }
+
+    pub fn tokenize_str(&self, s: &str) -> HashSet<String> {
+        let words = scan_for_unique_words_with(s, self.tokenizer.is_token_char())
+            .unwrap_or_default();
+        self.tokenizer.split_words(words)
+    }
+
+    /// The language of the stemmer filter, if there is one
+    pub fn stemmer_language(&self) -> Option<StemmerLanguage> {
+        self.filters.iter().find_map(|f| match f {
+            TokenFilter::Stemmer { language, .. } => Some(*language),
+            _ => None,
+        })
+    }
+
+    /// Picks the stemmer language for a document made of `tokens`,
+    /// detecting it if the stemmer filter is configured to.
+    /// Returns `None` if there is no stemmer filter
+    pub fn document_language(&self, tokens: &HashSet<String>) -> Option<StemmerLanguage> {
+        self.filters.iter().find_map(|f| match f {
+            TokenFilter::Stemmer { language: StemmerLanguage::Auto, fallback } =>
+                Some(StemmerLanguage::detect(tokens).unwrap_or(*fallback)),
+            TokenFilter::Stemmer { language, .. } => Some(*language),
+            _ => None,
+        })
+    }
+
+    /// Runs `token` through the filters, stemming it in `language` instead of the configured one if provided.
+    /// Returns the resulting term and the token as it was right before stemming
+    pub fn analyze_token(&self, token: &str, stage: Stage, language: Option<StemmerLanguage>) -> Option<(String, String)> {
+        let mut token = token.to_owned();
+        let mut surface = None;
+        for filter in &self.filters {
+            if let TokenFilter::Stemmer { .. } = filter {
+                surface.get_or_insert_with(|| token.clone());
+            }
+            token = filter.apply(token, stage, language)?;
+        }
+        let surface = surface.unwrap_or_else(|| token.clone());
+        Some((token, surface))
+    }
+
+    pub fn analyze_query_term(&self, token: &str, keep_stopwords: bool, language: Option<StemmerLanguage>) -> Option<String> {
+        self.analyze_token(token, Stage::Query { keep_stopwords }, language)
+            .map(|(term, _)| term)
+    }
+
+    /// Maps terms produced from document `tokens` to the shortest surface form they came from
+    pub fn analyze_tokens(&self, tokens: HashSet<String>, language: Option<StemmerLanguage>) -> HashMap<String, String> {
+        let mut terms = HashMap::<String, String>::new();
+        for (term, surface) in tokens.iter().filter_map(|t| self.analyze_token(t, Stage::Index, language)) {
+            match terms.get_mut(&term) {
+                Some(old) if (old.len(), &*old) <= (surface.len(), &surface) => {},
+                Some(old) => *old = surface,
+                None => { terms.insert(term, surface); },
+            }
+        }
+        terms
+    }
+
+    /// Applies only the filters that change the form of a token without dropping or stemming it.
+    /// Used for patterns that are matched against terms directly, like wildcards
+    pub fn normalize(&self, token: &str) -> String {
+        self.filters.iter()
+            .filter(|f| f.is_normalizer())
+            .fold(token.to_owned(), |token, f| f.apply(token, Stage::Index, None).unwrap())
+    }
+}
+
+impl Tokenizer {
+    /// Whether `chunk` is kept whole as one of the `recognize`d kinds of tokens
+    pub fn recognizes(&self, chunk: &str) -> bool {
+        self.recognize_token(chunk).is_some()
+    }
+
+    fn recognize_token<'a>(&self, chunk: &'a str) -> Option<&'a str> {
+        recognize_structured_token(chunk)
+            .filter(|(kind, _)| self.recognize.contains(kind))
+            .map(|(_, token)| token)
+    }
+
+    /// Structured tokens can contain any punctuation, so with recognizers enabled
+    /// the scanner only splits on whitespace and `split_words` does the rest
+    fn is_token_char(&self) -> impl Fn(char) -> bool + Copy + '_ {
+        move |c| match self.recognize.is_empty() {
+            true => self.is_word_char(c),
+            false => !c.is_whitespace(),
+        }
+    }
+
+    fn is_word_char(&self, c: char) -> bool {
+        match self.split_on {
+            SplitOn::NonAlphanumeric => is_word_char(c)
+                || (c == '-' && self.hyphens != HyphenMode::AsIs)
+                || (c == TYPOGRAPHIC_APOSTROPHE && self.apostrophes.typographic),
+            SplitOn::Whitespace => !c.is_whitespace(),
+        }
+    }
+
+    /// Splits words found by the scanner into final tokens
+    fn split_words(&self, words: HashSet<String>) -> HashSet<String> {
+        if self.recognize.is_empty() {
+            return self.split_plain_words(words);
+        }
+
+        let words_in = |s: &str| scan_for_unique_words_with(s, |c| self.is_word_char(c)).unwrap_or_default();
+        let mut tokens = HashSet::new();
+        let mut plain_words = HashSet::new();
+        for chunk in &words {
+            match self.recognize_token(chunk) {
+                Some(token) => {
+                    tokens.insert(token.to_owned());
+                    if self.sub_tokens {
+                        plain_words.extend(words_in(token));
+                    }
+                },
+                None => plain_words.extend(words_in(chunk)),
+            }
+        }
+        tokens.extend(self.split_plain_words(plain_words));
+        tokens
+    }
+
+    fn split_plain_words(&self, words: HashSet<String>) -> HashSet<String> {
+        words.into_iter()
+            .flat_map(|w| self.split_hyphens(w))
+            .flat_map(|w| self.apostrophes.apply(w))
+            .flat_map(|w| match self.cjk {
+                CjkMode::Runs => vec![w],
+                CjkMode::Bigrams => cjk_bigrams(&w),
+            })
+            .filter(|w| !w.is_empty())
+            .collect()
+    }
+
+    fn split_hyphens(&self, word: String) -> Vec<String> {
+        if self.hyphens == HyphenMode::AsIs || !word.contains('-') {
+            return vec![word];
+        }
+        let parts: Vec<&str> = word.split('-').filter(|p| !p.is_empty()).collect();
+        let joined = parts.concat();
+        let mut tokens = vec![];
+        if matches!(self.hyphens, HyphenMode::Parts | HyphenMode::PartsAndJoined) {
+            tokens.extend(parts.iter().map(|&p| p.to_owned()));
+        }
+        if matches!(self.hyphens, HyphenMode::Join | HyphenMode::PartsAndJoined) {
+            tokens.push(joined);
+        }
+        tokens
+    }
+}
+
+const TYPOGRAPHIC_APOSTROPHE: char = '\u{2019}';
+
+impl ApostropheRules {
+    fn apply(&self, word: String) -> Vec<String> {
+        let mut word = match self.typographic {
+            true => word.replace(TYPOGRAPHIC_APOSTROPHE, "'"),
+            false => word,
+        };
+        if self.trim {
+            word = word.trim_matches('\'').to_owned();
+        }
+        if self.possessives {
+            if let Some(stripped) = word.strip_suffix("'s").or_else(|| word.strip_suffix("'S")) {
+                word = stripped.trim_end_matches('\'').to_owned();
+            }
+        }
+        match self.contractions {
+            ContractionMode::Keep => vec![word],
+            ContractionMode::Split => word.split('\'').map(|p| p.to_owned()).collect(),
+            ContractionMode::Join => vec![word.replace('\'', "")],
+        }
+    }
+}
+
+impl TokenFilter {
+    fn apply(&self, token: String, stage: Stage, language: Option<StemmerLanguage>) -> Option<String> {
+        match self {
+            Self::Lowercase => Some(token.to_lowercase()),
+            Self::Stemmer { language: configured, fallback } => {
+                let language = match language.unwrap_or(*configured) {
+                    StemmerLanguage::Auto => *fallback,
+                    language => language,
+                };
+                Some(language.stem(token))
+            },
+            Self::Length { min, max } => {
+                let len = token.chars().count();
+                (*min..=*max).contains(&len).then_some(token)
+            },
+            Self::Stopwords(stopwords) => {
+                let keeps = matches!((stage, stopwords.config.remove_from),
+                    (Stage::Query { keep_stopwords: true }, _) | (Stage::Index, StopwordScope::Queries));
+                (keeps || !stopwords.words.contains(&token)).then_some(token)
+            },
+            Self::Normalize { form } => Some(match form {
+                NormalizationForm::Nfc => token.nfc().collect(),
+                NormalizationForm::Nfd => token.nfd().collect(),
+                NormalizationForm::Nfkc => token.nfkc().collect(),
+                NormalizationForm::Nfkd => token.nfkd().collect(),
+            }),
+            Self::CaseFold => Some(caseless::default_case_fold_str(&token)),
+            Self::RemoveDiacritics => Some(token.nfd()
+                .filter(|&c| !is_combining_mark(c))
+                .nfc()
+                .collect()),
+        }
+    }
+
+    fn is_normalizer(&self) -> bool {
+        matches!(self, Self::Lowercase | Self::Normalize { .. } | Self::CaseFold | Self::RemoveDiacritics)
+    }
+}
+
+impl TryFrom<StopwordsConfig> for Stopwords {
+    type Error = io::Error;
+
+    fn try_from(config: StopwordsConfig) -> io::Result<Self> {
+        let mut words: HashSet<String> = config.languages.iter()
+            .flat_map(|l| l.stopwords())
+            .map(|&w| w.to_owned())
+            .chain(config.words.iter().cloned())
+            .collect();
+        for file in &config.files {
+            let content = fs::read_to_string(file).map_err(|err|
+                io::Error::new(err.kind(), format!("error reading stopwords from {}: {}", file, err)))?;
+            words.extend(content.lines()
+                .map(|l| l.trim())
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(|l| l.to_owned()));
+        }
+        Ok(Self { config, words })
+    }
+}
+
+impl From<Stopwords> for StopwordsConfig {
+    fn from(stopwords: Stopwords) -> Self {
+        stopwords.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_token() {
+        let analyzer = Analyzer::default();
+        assert_eq!(analyzer.analyze_token("Movies", Stage::Index, None), Some(("movi".to_owned(), "movies".to_owned())));
+
+        let analyzer = Analyzer {
+            filters: vec![TokenFilter::Length { min: 2, max: 5 }, TokenFilter::Lowercase],
+            ..Default::default()
+        };
+        assert_eq!(analyzer.analyze_query_term("Movies", false, None), None);
+        assert_eq!(analyzer.analyze_query_term("Movie", false, None), Some("movie".to_owned()));
+        assert_eq!(analyzer.analyze_query_term("a", false, None), None);
+        assert_eq!(analyzer.normalize("Movies"), "movies");
+    }
+
+    #[test]
+    fn test_analyzer_config() {
+        let analyzer: Analyzer = serde_yaml::from_str("
+tokenizer:
+  split_on: whitespace
+filters:
+  - type: lowercase
+  - type: length
+    min: 3
+    max: 10
+").unwrap();
+
+        let terms = analyzer.analyze_tokens(analyzer.tokenize_str("It's a life-time mistake!"), None);
+        let mut terms: Vec<&str> = terms.keys().map(|t| t.as_str()).collect();
+        terms.sort();
+        assert_eq!(terms, vec!["it's", "life-time", "mistake!"]);
+
+        let analyzer: Analyzer = serde_yaml::from_str("{}").unwrap();
+        assert_eq!(analyzer.filters, Analyzer::default().filters);
+    }
+
+    #[test]
+    fn test_apostrophes_and_hyphens() {
+        let analyzer: Analyzer = serde_yaml::from_str("
+tokenizer:
+  apostrophes:
+    typographic: true
+    trim: true
+    possessives: true
+    contractions: join
+  hyphens: parts_and_joined
+filters:
+  - type: lowercase
+").unwrap();
+        let tokens = |s| {
+            let mut tokens: Vec<String> = analyzer.tokenize_str(s).into_iter().collect();
+            tokens.sort();
+            tokens
+        };
+
+        assert_eq!(tokens("'Kaalai' -- Rajini's life-time role"),
+            vec!["Kaalai", "Rajini", "life", "lifetime", "role", "time"]);
+        assert_eq!(tokens("Don’t miss the actors' lines"), vec!["Dont", "actors", "lines", "miss", "the"]);
+        assert_eq!(tokens("-non-stop-"), vec!["non", "nonstop", "stop"]);
+        assert_eq!(analyzer.tokenize_reader("Rajini’s".as_bytes()).unwrap(), HashSet::from(["Rajini".to_owned()]));
+
+        let analyzer: Analyzer = serde_yaml::from_str("
+tokenizer:
+  apostrophes:
+    contractions: split
+  hyphens: join
+").unwrap();
+        let mut tokens: Vec<String> = analyzer.tokenize_str("can't life-time").into_iter().collect();
+        tokens.sort();
+        assert_eq!(tokens, vec!["can", "lifetime", "t"]);
+
+        let tokens = Analyzer::default().tokenize_str("'Kaalai' life-time");
+        assert!(tokens.contains("'Kaalai'") && tokens.contains("life") && tokens.contains("time"));
+    }
+
+    #[test]
+    fn test_structured_tokens() {
+        let analyzer: Analyzer = serde_yaml::from_str("
+tokenizer:
+  recognize: [url, email, number]
+filters:
+  - type: lowercase
+").unwrap();
+        let tokens = |s| {
+            let mut tokens: Vec<String> = analyzer.tokenize_str(s).into_iter().collect();
+            tokens.sort();
+            tokens
+        };
+
+        assert_eq!(tokens("See http://imdb.com/title/tt123, rated 1/10 by user@example.com #bad"),
+            vec!["1/10", "See", "bad", "by", "http://imdb.com/title/tt123", "rated", "user@example.com"]);
+        assert!(analyzer.tokenizer.recognizes("http://imdb.com/find?q=simbu"));
+        assert!(!analyzer.tokenizer.recognizes("#bad"));
+
+        let analyzer = Analyzer {
+            tokenizer: Tokenizer { sub_tokens: true, ..analyzer.tokenizer },
+            ..analyzer
+        };
+        let tokens = analyzer.tokenize_reader("mail user@example.com".as_bytes()).unwrap();
+        assert!(["user@example.com", "user", "example", "com", "mail"].iter().all(|t| tokens.contains(*t)));
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let stopwords_file = std::env::temp_dir().join(format!("parallel_computing_test_stopwords_{}.txt", std::process::id()));
+        fs::write(&stopwords_file, "# tags left over from html\nbr\n\nnbsp\n").unwrap();
+
+        let analyzer: Analyzer = serde_yaml::from_str(&format!("
+filters:
+  - type: lowercase
+  - type: stopwords
+    languages: [english]
+    words: [movie]
+    files: [{:?}]
+  - type: stemmer
+", stopwords_file)).unwrap();
+        fs::remove_file(&stopwords_file).unwrap();
+
+        let terms = analyzer.analyze_tokens(analyzer.tokenize_str("The movie is a BR masterpiece<br /><br />"), None);
+        assert_eq!(terms.into_keys().collect::<Vec<_>>(), vec!["masterpiec"]);
+
+        let analyzer: Analyzer = serde_yaml::from_str("
+tokenizer:
+  apostrophes:
+    contractions: split
+filters:
+  - type: lowercase
+  - type: stopwords
+    languages: [french]
+").unwrap();
+        let mut terms: Vec<String> = analyzer.analyze_tokens(analyzer.tokenize_str("L'histoire d'amour"), None).into_keys().collect();
+        terms.sort();
+        assert_eq!(terms, vec!["amour", "histoire"]);
+        assert_eq!(analyzer.analyze_query_term("the", true, None), Some("the".to_owned()));
+
+        let analyzer: Analyzer = serde_yaml::from_str("
+filters:
+  - type: stopwords
+    languages: [english]
+    remove_from: queries
+").unwrap();
+        assert_eq!(analyzer.analyze_token("the", Stage::Index, None), Some(("the".to_owned(), "the".to_owned())));
+        assert_eq!(analyzer.analyze_query_term("the", false, None), None);
+        assert_eq!(analyzer.analyze_query_term("the", true, None), Some("the".to_owned()));
+
+        let missing_file = serde_yaml::from_str::<Analyzer>("
+filters:
+  - type: stopwords
+    files: [/nonexistent/stopwords.txt]
+");
+        assert!(missing_file.is_err());
+    }
+
+    #[test]
+    fn test_stemmer_languages() {
+        let analyzer: Analyzer = serde_yaml::from_str("
+filters:
+  - type: lowercase
+  - type: stemmer
+    language: auto
+    fallback: none
+").unwrap();
+
+        let french = analyzer.tokenize_str("Les acteurs jouent magnifiquement dans ce film, mais l'histoire est trop longue et les dialogues sont ennuyeux");
+        assert_eq!(analyzer.document_language(&french), Some(StemmerLanguage::French));
+        let english = analyzer.tokenize_str("The actors are playing wonderfully in this movie, but the story is far too long and the dialogues are boring");
+        assert_eq!(analyzer.document_language(&english), Some(StemmerLanguage::English));
+        let japanese = analyzer.tokenize_str("アニャ likes peanuts, ワクワク!");
+        assert_eq!(analyzer.document_language(&japanese), Some(StemmerLanguage::None));
+
+        let terms = analyzer.analyze_tokens(japanese, Some(StemmerLanguage::None));
+        assert!(terms.contains_key("ワクワク") && terms.contains_key("likes"));
+        assert_eq!(analyzer.analyze_query_term("acteurs", false, Some(StemmerLanguage::French)), Some("acteur".to_owned()));
+        assert_eq!(analyzer.analyze_query_term("playing", false, Some(StemmerLanguage::English)), Some("play".to_owned()));
+        assert_eq!(analyzer.analyze_query_term("playing", false, None), Some("playing".to_owned()));
+    }
+
+    #[test]
+    fn test_cjk_bigram_tokenizer() {
+        let analyzer: Analyzer = serde_yaml::from_str("
+tokenizer:
+  cjk: bigrams
+").unwrap();
+
+        let mut tokens: Vec<String> = analyzer.tokenize_str("アニャ likes peanuts, ワクワク!").into_iter().collect();
+        tokens.sort();
+        assert_eq!(tokens, vec!["likes", "peanuts", "アニ", "クワ", "ニャ", "ワク"]);
+
+        let tokens = analyzer.tokenize_reader("東京タワーへ行く".as_bytes()).unwrap();
+        assert!(tokens.contains("タワ") && tokens.contains("ワー"));
+    }
+
+    #[test]
+    fn test_unicode_folding() {
+        let analyzer: Analyzer = serde_yaml::from_str("
+filters:
+  - type: normalize
+    form: nfkc
+  - type: case_fold
+").unwrap();
+        let term = |token| analyzer.analyze_query_term(token, false, None).unwrap();
+        assert_eq!(term("caf\u{e9}"), term("cafe\u{301}"));
+        assert_eq!(term("Stra\u{df}e"), "strasse");
+        assert_eq!(term("STRASSE"), "strasse");
+        assert_eq!(term("\u{fb01}lm"), "film");
+        assert_ne!(term("café"), "cafe");
+
+        let analyzer: Analyzer = serde_yaml::from_str("
+filters:
+  - type: lowercase
+  - type: remove_diacritics
+").unwrap();
+        let term = |token| analyzer.analyze_query_term(token, false, None).unwrap();
+        assert_eq!(term("Café"), "cafe");
+        assert_eq!(term("cafe\u{301}"), "cafe");
+        assert_eq!(term("Ångström"), "angstrom");
+        assert_eq!(analyzer.normalize("Cafe\u{301}*"), "cafe*");
+
+        // documents are read in 264 byte chunks, so these paddings put a chunk boundary
+        // around the decomposed accent
+        for padding in 255..=265 {
+            let content = format!("{}cafe\u{301}", " ".repeat(padding));
+            let tokens = analyzer.tokenize_reader(content.as_bytes()).unwrap();
+            let terms = analyzer.analyze_tokens(tokens, None);
+            assert_eq!(terms.into_keys().collect::<Vec<_>>(), vec!["cafe"], "padding {}", padding);
+        }
+    }
+}
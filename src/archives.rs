@@ -0,0 +1,265 @@
+use std::{fs::File, io::{self, Read, BufReader}, path::Path};
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+/// Separates the path of an archive from the name of a member in a document path,
+/// like in `corpus.tar.gz!/train/pos/1_7.txt`
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+/// Largest document read from an archive or a compressed file. Sizes in archive headers are not trusted,
+/// so a truncated archive or a decompression bomb fails instead of taking all memory
+pub const MAX_DOCUMENT_SIZE: u64 = 256 << 20;
+
+/// Formats of compressed files and archives that documents are read from directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// A single compressed document, addressed by the path of the `.gz` file itself
+    Gzip,
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".gz") {
+            Some(Self::Gzip)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Calls `f` with the document path and the content of every regular file in the archive at `path`
+pub fn for_each_member(path: &Path, kind: ArchiveKind, mut f: impl FnMut(String, Vec<u8>)) -> io::Result<()> {
+    let archive_path = path.to_string_lossy();
+    let file = BufReader::new(File::open(path)?);
+    match kind {
+        ArchiveKind::Gzip => f(archive_path.into_owned(), read_limited(GzDecoder::new(file), MAX_DOCUMENT_SIZE)?),
+        ArchiveKind::Tar => for_each_tar_member(file, &archive_path, f)?,
+        ArchiveKind::TarGz => for_each_tar_member(GzDecoder::new(file), &archive_path, f)?,
+        ArchiveKind::Zip => {
+            let mut archive = ZipArchive::new(file)?;
+            for i in 0..archive.len() {
+                let mut member = archive.by_index(i)?;
+                if !member.is_file() {
+                    continue;
+                }
+                let content = read_limited(&mut member, MAX_DOCUMENT_SIZE)?;
+                f(member_path(&archive_path, member.name()), content);
+            }
+        },
+    }
+    Ok(())
+}
+
+fn for_each_tar_member(reader: impl Read, archive_path: &str, mut f: impl FnMut(String, Vec<u8>)) -> io::Result<()> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        f(member_path(archive_path, &name), read_tar_entry(&mut entry)?);
+    }
+    Ok(())
+}
+
+/// Reads all of `reader`, failing if it holds more than `limit` bytes
+fn read_limited(reader: impl Read, limit: u64) -> io::Result<Vec<u8>> {
+    let mut content = vec![];
+    reader.take(limit + 1).read_to_end(&mut content)?;
+    if content.len() as u64 > limit {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("document larger than {} bytes", limit)));
+    }
+    Ok(content)
+}
+
+/// Reads the content of a tar entry, failing if the archive ends before the size in its header
+fn read_tar_entry(entry: &mut tar::Entry<'_, impl Read>) -> io::Result<Vec<u8>> {
+    let content = read_limited(&mut *entry, MAX_DOCUMENT_SIZE)?;
+    if (content.len() as u64) < entry.size() {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "archive member is truncated"));
+    }
+    Ok(content)
+}
+
+fn member_path(archive_path: &str, name: &str) -> String {
+    format!("{}{}{}", archive_path, MEMBER_SEPARATOR, name.trim_start_matches("./"))
+}
+
+/// Whether `document` can't be read as a regular file: it is either an archive member or a compressed file
+pub fn is_archived(document: &str) -> bool {
+    document.contains(MEMBER_SEPARATOR) || ArchiveKind::from_path(Path::new(document)) == Some(ArchiveKind::Gzip)
+}
+
+/// Reads the content of a document read with `for_each_member`, decompressing only the member it names
+pub fn read_document(document: &str) -> io::Result<Vec<u8>> {
+    let (archive_path, member) = match document.split_once(MEMBER_SEPARATOR) {
+        Some((archive_path, member)) => (Path::new(archive_path), Some(member)),
+        None => (Path::new(document), None),
+    };
+    let kind = ArchiveKind::from_path(archive_path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not an archive"))?;
+
+    let file = BufReader::new(File::open(archive_path)?);
+    let content = match (kind, member) {
+        (ArchiveKind::Gzip, _) => Some(read_limited(GzDecoder::new(file), MAX_DOCUMENT_SIZE)?),
+        (_, None) => None,
+        (ArchiveKind::Tar, Some(member)) => read_tar_member(file, member)?,
+        (ArchiveKind::TarGz, Some(member)) => read_tar_member(GzDecoder::new(file), member)?,
+        (ArchiveKind::Zip, Some(member)) => {
+            let mut archive = ZipArchive::new(file)?;
+            // Member paths have a leading `./` trimmed, so look up the name as stored in the archive
+            let index = archive.file_names()
+                .find(|name| name.trim_start_matches("./") == member)
+                .and_then(|name| archive.index_for_name(name));
+            match index {
+                Some(index) => {
+                    let mut member = archive.by_index(index)?;
+                    match member.is_file() {
+                        true => Some(read_limited(&mut member, MAX_DOCUMENT_SIZE)?),
+                        false => None,
+                    }
+                },
+                None => None,
+            }
+        },
+    };
+    content.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "archive member does not exist"))
+}
+
+/// Reads the first regular file named `member`, leaving the content of the entries before it unread
+fn read_tar_member(reader: impl Read, member: &str) -> io::Result<Option<Vec<u8>>> {
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() || entry.path()?.to_string_lossy().trim_start_matches("./") != member {
+            continue;
+        }
+        return read_tar_entry(&mut entry).map(Some);
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use flate2::{write::GzEncoder, Compression};
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+
+    fn tar_bytes(members: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (name, content) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content.as_bytes()).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip_bytes(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_archives() {
+        let directory = std::env::temp_dir().join(format!("parallel_computing_test_archives_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let members = [("train/pos/1_7.txt", "great"), ("./train/neg/2_1.txt", "awful")];
+        fs::write(directory.join("corpus.tar"), tar_bytes(&members)).unwrap();
+        fs::write(directory.join("corpus.tar.gz"), gzip_bytes(&tar_bytes(&members))).unwrap();
+        fs::write(directory.join("review.txt.gz"), gzip_bytes(b"gzipped")).unwrap();
+
+        let mut zip = ZipWriter::new(fs::File::create(directory.join("corpus.zip")).unwrap());
+        zip.add_directory("train/", SimpleFileOptions::default()).unwrap();
+        for (name, content) in &members[..1] {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let d = directory.to_string_lossy();
+        let test_cases = vec![
+            ("corpus.tar", vec![
+                (format!("{}/corpus.tar!/train/pos/1_7.txt", d), "great"),
+                (format!("{}/corpus.tar!/train/neg/2_1.txt", d), "awful"),
+            ]),
+            ("corpus.tar.gz", vec![
+                (format!("{}/corpus.tar.gz!/train/pos/1_7.txt", d), "great"),
+                (format!("{}/corpus.tar.gz!/train/neg/2_1.txt", d), "awful"),
+            ]),
+            ("corpus.zip", vec![(format!("{}/corpus.zip!/train/pos/1_7.txt", d), "great")]),
+            ("review.txt.gz", vec![(format!("{}/review.txt.gz", d), "gzipped")]),
+        ];
+
+        for (name, expected) in test_cases {
+            let path = directory.join(name);
+            let mut documents = vec![];
+            for_each_member(&path, ArchiveKind::from_path(&path).unwrap(), |path, bytes| {
+                documents.push((path, String::from_utf8(bytes).unwrap()));
+            }).unwrap();
+
+            let expected: Vec<(String, String)> = expected.into_iter().map(|(p, c)| (p, c.to_owned())).collect();
+            assert_eq!(documents, expected, "archive {}", name);
+            for (document, content) in &documents {
+                assert!(is_archived(document));
+                assert_eq!(&read_document(document).unwrap(), content.as_bytes(), "document {}", document);
+            }
+        }
+
+        for archive in ["corpus.zip", "corpus.tar", "corpus.tar.gz"] {
+            let missing = read_document(&format!("{}/{}!/missing.txt", d, archive));
+            assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound, "archive {}", archive);
+        }
+        let directory_member = read_document(&format!("{}/corpus.zip!/train/", d));
+        assert_eq!(directory_member.unwrap_err().kind(), io::ErrorKind::NotFound);
+        assert!(!is_archived("reviews/1_7.txt"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_archive() {
+        let directory = std::env::temp_dir().join(format!("parallel_computing_test_truncated_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        // a header claiming an 8 GiB member followed by only 5 bytes of it
+        let mut header = tar::Header::new_gnu();
+        header.set_path("huge.txt").unwrap();
+        header.set_size((8 << 30) - 1);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend_from_slice(b"great");
+        fs::write(directory.join("truncated.tar"), &bytes).unwrap();
+        fs::write(directory.join("truncated.tar.gz"), gzip_bytes(&bytes)).unwrap();
+
+        for name in ["truncated.tar", "truncated.tar.gz"] {
+            let path = directory.join(name);
+            let result = for_each_member(&path, ArchiveKind::from_path(&path).unwrap(), |_, _| panic!("no member is complete"));
+            assert!(result.is_err(), "archive {}", name);
+            assert!(read_document(&format!("{}!/huge.txt", path.to_string_lossy())).is_err(), "archive {}", name);
+        }
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(read_limited(&b"great"[..], 5).unwrap(), b"great");
+        assert_eq!(read_limited(&b"great"[..], 4).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -2,7 +2,7 @@ use std::{net::TcpStream, io};
 
 use clap::{Parser, ValueEnum};
 use log::{info, debug, warn, error};
-use parallel_computing::messages::{Request, IntoMessage, Response, FromMessage, MessageContent};
+use parallel_computing::{messages::{Request, IntoMessage, Response, FromMessage, MessageContent, QueryRequest, MoreLikeThisRequest, StatsRequest}, inverted_index::{QueryOptions, SortBy}, stemming::StemmerLanguage};
 
 #[derive(Parser, Debug)]
 struct Arguments {
@@ -13,7 +13,62 @@ struct Arguments {
     request_kind: RequestKindCli,
 
     #[arg(short = 'p', long = "payload")]
-    payload: Option<String>
+    payload: Option<String>,
+
+    #[arg(long = "sort-by", default_value = "rank")]
+    sort_by: SortByCli,
+
+    #[arg(long = "reverse")]
+    reverse: bool,
+
+    #[arg(long = "max-expansions")]
+    max_expansions: Option<usize>,
+
+    #[arg(long = "fuzzy")]
+    fuzzy: bool,
+
+    #[arg(long = "fuzzy-distance")]
+    fuzzy_distance: Option<usize>,
+
+    #[arg(long = "fuzzy-prefix-length")]
+    fuzzy_prefix_length: Option<usize>,
+
+    #[arg(long = "max-suggestions")]
+    max_suggestions: Option<usize>,
+
+    #[arg(long = "keep-stopwords")]
+    keep_stopwords: bool,
+
+    /// Stemmer language for query words, e.g. `english`, `french` or `none`
+    #[arg(long = "language", value_parser = parse_language)]
+    language: Option<StemmerLanguage>,
+
+    /// Attribute to count the values of among the results, e.g. `ext`, `dir` or a metadata key
+    #[arg(long = "facet", action = clap::ArgAction::Append)]
+    facets: Vec<String>,
+
+    #[arg(long = "max-facet-values")]
+    max_facet_values: Option<usize>,
+
+    /// Shows how the rank of every result was computed
+    #[arg(long = "explain")]
+    explain: bool,
+
+    /// Number of the document's most distinctive terms a `similar` request looks up
+    #[arg(long = "max-terms")]
+    max_terms: Option<usize>,
+
+    /// Number of the most similar documents a `similar` request returns
+    #[arg(long = "max-results")]
+    max_results: Option<usize>,
+
+    /// Number of most frequent terms and longest posting lists a `stats` request lists
+    #[arg(long = "top")]
+    top: Option<usize>,
+}
+
+fn parse_language(s: &str) -> Result<StemmerLanguage, String> {
+    serde_yaml::from_str(s).map_err(|_| format!("unknown language `{}`", s))
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -21,6 +76,28 @@ enum RequestKindCli {
     Ping,
     Index,
     File,
+    Similar,
+    Stats,
+    Term,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum SortByCli {
+    Rank,
+    Path,
+    Modified,
+    Length,
+}
+
+impl From<SortByCli> for SortBy {
+    fn from(value: SortByCli) -> Self {
+        match value {
+            SortByCli::Rank => SortBy::Rank,
+            SortByCli::Path => SortBy::Path,
+            SortByCli::Modified => SortBy::Modified,
+            SortByCli::Length => SortBy::Length,
+        }
+    }
 }
 
 fn main() -> io::Result<()> {
@@ -36,10 +113,43 @@ fn main() -> io::Result<()> {
             }
             Request::Ping
         },
-        (RequestKindCli::Index, Some(query)) => 
-            Request::Query(query.to_string()),
+        (RequestKindCli::Stats, x) => {
+            if x.is_some() {
+                warn!("stats request does not require a payload")
+            }
+            Request::Stats(StatsRequest { top: arguments.top.unwrap_or(StatsRequest::default().top) })
+        },
+        (RequestKindCli::Index, Some(query)) => {
+            let defaults = QueryOptions::default();
+            Request::QueryWithOptions(QueryRequest {
+                query,
+                options: QueryOptions {
+                    sort_by: arguments.sort_by.into(),
+                    reverse: arguments.reverse,
+                    max_expansions: arguments.max_expansions.unwrap_or(defaults.max_expansions),
+                    fuzzy: arguments.fuzzy,
+                    fuzzy_distance: arguments.fuzzy_distance.unwrap_or(defaults.fuzzy_distance),
+                    fuzzy_prefix_length: arguments.fuzzy_prefix_length.unwrap_or(defaults.fuzzy_prefix_length),
+                    max_suggestions: arguments.max_suggestions.unwrap_or(defaults.max_suggestions),
+                    keep_stopwords: arguments.keep_stopwords,
+                    language: arguments.language,
+                    facets: arguments.facets,
+                    max_facet_values: arguments.max_facet_values.unwrap_or(defaults.max_facet_values),
+                    explain: arguments.explain,
+                },
+            })
+        },
         (RequestKindCli::File, Some(filepath)) => 
             Request::QueryFile(filepath.to_string()),
+        (RequestKindCli::Similar, Some(document)) => {
+            let request = MoreLikeThisRequest::new(document);
+            Request::MoreLikeThis(MoreLikeThisRequest {
+                max_terms: arguments.max_terms.unwrap_or(request.max_terms),
+                max_results: arguments.max_results.unwrap_or(request.max_results),
+                ..request
+            })
+        },
+        (RequestKindCli::Term, Some(word)) => Request::Term(word),
 
         (request_kind, None) => {
             error!("{:?} request requires a payload", request_kind);
@@ -60,8 +170,57 @@ fn main() -> io::Result<()> {
             std::process::exit(1)
         },
         Response::QueryResult(res) => {
-            for query_res in res {
-                println!("rank: {}; document: {}", query_res.rank, query_res.document)
+            for query_res in &res.search.results {
+                println!("rank: {}; document: {}", query_res.rank, query_res.document);
+                for (field, text) in &query_res.fields {
+                    println!("    {}: {}", field, text);
+                }
+                for clause in &query_res.explanation {
+                    println!("    `{}` scored {}, terms: {}", clause.clause, clause.score, clause.terms.join(", "));
+                    for term in &clause.matches {
+                        println!("        {}:{} {} * boost {} = {}",
+                            term.field, term.term, term.weight, term.boost, term.weight * term.boost);
+                    }
+                }
+            }
+            if res.search.results.is_empty() {
+                println!("nothing found");
+                if !res.suggestions.is_empty() {
+                    println!("did you mean: {}", res.suggestions.join(", "));
+                }
+            }
+            for (attribute, counts) in &res.search.facets {
+                let counts: Vec<String> = counts.iter()
+                    .map(|c| format!("{} ({})", c.value, c.count))
+                    .collect();
+                println!("{}: {}", attribute, counts.join(", "));
+            }
+        },
+        Response::Stats(stats) => {
+            println!("documents: {}", stats.documents);
+            println!("terms: {}", stats.terms);
+            println!("postings: {}", stats.postings);
+            println!("memory estimate: {} bytes", stats.memory_estimate);
+            println!("query cache: {} of {} entries, {} bytes, {} hits, {} misses", stats.query_cache.entries,
+                stats.query_cache.capacity, stats.query_cache.bytes, stats.query_cache.hits, stats.query_cache.misses);
+            println!("most frequent terms:");
+            for term in &stats.most_frequent_terms {
+                println!("    {} ({}): {}", term.term, term.surface, term.postings);
+            }
+            println!("longest posting lists:");
+            for term in &stats.longest_postings {
+                println!("    {}:{} ({}): {}", term.field.as_deref().unwrap_or_default(), term.term, term.surface, term.postings);
+            }
+        },
+        Response::TermInfo(infos) => {
+            if infos.is_empty() {
+                println!("term not found");
+            }
+            for info in infos {
+                println!("{}:{} ({}): {} documents", info.field, info.term, info.surface, info.document_frequency);
+                for document in &info.documents {
+                    println!("    {}", document);
+                }
             }
         },
         Response::FileResult(file) => {
@@ -0,0 +1,219 @@
+use std::{fmt, io};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use serde::{Serialize, Deserialize};
+
+/// What is done with a file that is not valid UTF-8 and has no byte order mark
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidUtf8 {
+    /// The file is not indexed
+    Fail,
+    /// The file is decoded as Windows-1252, a superset of Latin-1
+    #[default]
+    Windows1252,
+    /// Invalid sequences are replaced with U+FFFD and the rest is decoded as UTF-8
+    Lossy,
+}
+
+/// Something that went wrong while decoding a file that was still indexed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodeWarning {
+    /// The file was not valid UTF-8 and was decoded with another encoding
+    Fallback { encoding: &'static str },
+    /// Invalid sequences were replaced
+    Replaced { count: usize },
+}
+
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fallback { encoding } => write!(f, "not valid UTF-8, decoded as {}", encoding),
+            Self::Replaced { count } => write!(f, "{} invalid sequences replaced", count),
+        }
+    }
+}
+
+/// Why a file was recognized as binary and not indexed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryContent {
+    /// The file starts with the signature of a known binary format
+    Format { name: &'static str },
+    /// The file contains zero bytes, which text does not
+    NulBytes,
+}
+
+impl fmt::Display for BinaryContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Format { name } => write!(f, "binary file: {}", name),
+            Self::NulBytes => write!(f, "binary file: contains NUL bytes"),
+        }
+    }
+}
+
+/// Signatures at the start of common binary formats
+const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (b"\xff\xd8\xff", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"%PDF-", "PDF document"),
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"\x1f\x8b", "gzip archive"),
+    (b"\xfd7zXZ\x00", "xz archive"),
+    (b"7z\xbc\xaf\x27\x1c", "7z archive"),
+    (b"\x7fELF", "ELF executable"),
+    (b"\xca\xfe\xba\xbe", "Java class or Mach-O executable"),
+    (b"\xcf\xfa\xed\xfe", "Mach-O executable"),
+    (b"\x00asm", "WebAssembly module"),
+    (b"SQLite format 3\x00", "SQLite database"),
+];
+
+/// How much of a file is looked at for zero bytes
+pub const SNIFF_LENGTH: usize = 8000;
+
+/// Recognizes binary content by a known signature or by zero bytes near the start.
+/// Text with a byte order mark and UTF-16 text, which is full of zero bytes, are not binary
+pub fn sniff_binary(bytes: &[u8]) -> Option<BinaryContent> {
+    if let Some(&(_, name)) = MAGIC_NUMBERS.iter().find(|(magic, _)| bytes.starts_with(magic)) {
+        return Some(BinaryContent::Format { name });
+    }
+    if bytes.get(257..262) == Some(b"ustar") {
+        return Some(BinaryContent::Format { name: "tar archive" });
+    }
+    if Encoding::for_bom(bytes).is_some() || sniff_utf16(bytes).is_some() {
+        return None;
+    }
+    bytes[..bytes.len().min(SNIFF_LENGTH)].contains(&0).then_some(BinaryContent::NulBytes)
+}
+
+/// Whether `decode` reads a file starting with `head` as UTF-8, which is when there is no byte order mark
+/// and it doesn't look like UTF-16. Such a file can be tokenized while it is read, without decoding it first
+pub fn is_utf8_candidate(head: &[u8]) -> bool {
+    Encoding::for_bom(head).is_none() && sniff_utf16(head).is_none()
+}
+
+/// Decodes the content of a file into text.
+///
+/// A byte order mark picks UTF-8, UTF-16LE or UTF-16BE. Without one, UTF-16 is recognized by the
+/// zero bytes in ASCII characters, and anything else is expected to be UTF-8, with `invalid_utf8`
+/// deciding what happens if it isn't
+pub fn decode(bytes: &[u8], invalid_utf8: InvalidUtf8) -> io::Result<(String, Option<DecodeWarning>)> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return Ok(decode_with(encoding, &bytes[bom_length..]));
+    }
+    if let Some(encoding) = sniff_utf16(bytes) {
+        return Ok(decode_with(encoding, bytes));
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Ok((text.to_owned(), None));
+    }
+
+    match invalid_utf8 {
+        InvalidUtf8::Fail => Err(io::Error::new(io::ErrorKind::InvalidData, "string contains invalid UTF8")),
+        InvalidUtf8::Windows1252 => {
+            let (text, _) = WINDOWS_1252.decode_without_bom_handling(bytes);
+            Ok((text.into_owned(), Some(DecodeWarning::Fallback { encoding: WINDOWS_1252.name() })))
+        },
+        InvalidUtf8::Lossy => {
+            let count = bytes.utf8_chunks().filter(|c| !c.invalid().is_empty()).count();
+            let text = String::from_utf8_lossy(bytes).into_owned();
+            Ok((text, Some(DecodeWarning::Replaced { count })))
+        },
+    }
+}
+
+fn decode_with(encoding: &'static Encoding, bytes: &[u8]) -> (String, Option<DecodeWarning>) {
+    let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+    let warning = had_errors.then(|| DecodeWarning::Replaced {
+        count: text.chars().filter(|&c| c == char::REPLACEMENT_CHARACTER).count(),
+    });
+    (text.into_owned(), warning)
+}
+
+/// Recognizes UTF-16 without a byte order mark: mostly ASCII text in UTF-16 has a zero byte
+/// in every other position
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(1024) & !1];
+    let pairs = head.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let zeros_at = |parity: usize| head.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count();
+    let (even_zeros, odd_zeros) = (zeros_at(0), zeros_at(1));
+
+    // most, but not necessarily all, characters are expected to be ASCII
+    if odd_zeros * 10 >= pairs * 7 && even_zeros * 10 <= pairs {
+        Some(UTF_16LE)
+    } else if even_zeros * 10 >= pairs * 7 && odd_zeros * 10 <= pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(s: &str, little_endian: bool) -> Vec<u8> {
+        s.encode_utf16()
+            .flat_map(|u| match little_endian {
+                true => u.to_le_bytes(),
+                false => u.to_be_bytes(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        let text = "Café au lait";
+        let mut utf16_bom = vec![0xFF, 0xFE];
+        utf16_bom.extend(utf16(text, true));
+        let mut utf8_bom = vec![0xEF, 0xBB, 0xBF];
+        utf8_bom.extend(text.as_bytes());
+
+        let test_cases = vec![
+            (text.as_bytes().to_vec(), InvalidUtf8::Fail, text, None),
+            (utf8_bom, InvalidUtf8::Fail, text, None),
+            (utf16_bom, InvalidUtf8::Fail, text, None),
+            (utf16(text, true), InvalidUtf8::Fail, text, None),
+            (utf16(text, false), InvalidUtf8::Fail, text, None),
+            (b"Caf\xe9 au lait".to_vec(), InvalidUtf8::Windows1252, text,
+                Some(DecodeWarning::Fallback { encoding: "windows-1252" })),
+            (b"Caf\xe9 au l\xffait".to_vec(), InvalidUtf8::Lossy, "Caf\u{FFFD} au l\u{FFFD}ait",
+                Some(DecodeWarning::Replaced { count: 2 })),
+        ];
+
+        for (bytes, invalid_utf8, expected_text, expected_warning) in test_cases {
+            let (text, warning) = decode(&bytes, invalid_utf8).unwrap();
+            assert_eq!(text, expected_text, "bytes {:?}", bytes);
+            assert_eq!(warning, expected_warning, "bytes {:?}", bytes);
+        }
+
+        assert!(decode(b"Caf\xe9", InvalidUtf8::Fail).is_err());
+    }
+
+    #[test]
+    fn test_sniff_binary() {
+        let mut tar = vec![b'a'; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+
+        let test_cases: Vec<(Vec<u8>, Option<BinaryContent>)> = vec![
+            (b"\x89PNG\r\n\x1a\n\x00\x00".to_vec(), Some(BinaryContent::Format { name: "PNG image" })),
+            (b"PK\x03\x04rest".to_vec(), Some(BinaryContent::Format { name: "ZIP archive" })),
+            (tar, Some(BinaryContent::Format { name: "tar archive" })),
+            (b"text\x00with a zero byte".to_vec(), Some(BinaryContent::NulBytes)),
+            (utf16("UTF-16 text", true), None),
+            (b"Caf\xe9 au lait".to_vec(), None),
+            (vec![], None),
+        ];
+
+        for (bytes, expected) in test_cases {
+            assert_eq!(sniff_binary(&bytes), expected, "bytes {:?}", bytes);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{path::Path, borrow::Cow};
+
+use pulldown_cmark::{Event, Parser};
+
+use crate::decoding::SNIFF_LENGTH;
+
+/// Starts of HTML tags that `ContentType::sniff` looks for, in lowercase
+const HTML_MARKERS: &[&str] = &["<!doctype", "<html", "<br", "<p>", "<p ", "<div", "<span", "<a ", "<b>", "<i>"];
+
+/// Format of a document's content, which decides how its text is extracted before tokenization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    PlainText,
+    Html,
+    Markdown,
+}
+
+impl ContentType {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "html" | "htm" | "xhtml" => Some(Self::Html),
+            "md" | "markdown" => Some(Self::Markdown),
+            _ => None,
+        }
+    }
+
+    /// Guesses the content type from the start of the content.
+    /// Text with anything that looks like an HTML tag in its first `SNIFF_LENGTH` bytes, like a `<br />` in a review, is HTML
+    pub fn sniff(content: &str) -> Self {
+        let head = &content.as_bytes()[..content.len().min(SNIFF_LENGTH)];
+        let has_markup = head.iter().enumerate()
+            .filter(|&(_, &b)| b == b'<')
+            .any(|(i, _)| HTML_MARKERS.iter()
+                .any(|marker| head[i..].get(..marker.len()).is_some_and(|s| s.eq_ignore_ascii_case(marker.as_bytes()))));
+        match has_markup {
+            true => Self::Html,
+            false => Self::PlainText,
+        }
+    }
+
+    /// Picks the content type by the extension of `path`, sniffing `content` if the extension says nothing
+    pub fn detect(path: &Path, content: &str) -> Self {
+        Self::from_extension(path).unwrap_or_else(|| Self::sniff(content))
+    }
+
+    /// Extracts the text meant for a reader, dropping markup
+    pub fn extract_text(self, content: &str) -> Cow<'_, str> {
+        match self {
+            Self::PlainText => Cow::Borrowed(content),
+            Self::Html => Cow::Owned(html_to_text(content)),
+            Self::Markdown => Cow::Owned(markdown_to_text(content)),
+        }
+    }
+}
+
+/// Strips tags, comments, scripts and styles and decodes entities.
+///
+/// Every tag is replaced with a space, so words on both sides of a `<br />` are not glued together.
+/// A `<` that doesn't start a tag, like in `1 < 2`, is kept as text
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(i) = rest.find('<') {
+        text.push_str(&rest[..i]);
+        let tag = &rest[i..];
+
+        if let Some(comment) = tag.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            text.push(' ');
+            continue;
+        }
+        let starts_tag = tag[1..].starts_with(|c: char| c.is_ascii_alphabetic() || matches!(c, '/' | '!' | '?'));
+        let Some(end) = tag.find('>').filter(|_| starts_tag) else {
+            text.push('<');
+            rest = &tag[1..];
+            continue;
+        };
+
+        let name: String = tag[1..].chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        rest = &tag[end + 1..];
+        if name == "script" || name == "style" {
+            // lowercasing ASCII keeps byte offsets, so positions found in it are valid in `rest`
+            let closing = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&closing) {
+                Some(close) => rest[close..].find('>').map_or("", |end| &rest[close + end + 1..]),
+                None => "",
+            };
+        }
+        text.push(' ');
+    }
+    text.push_str(rest);
+
+    html_escape::decode_html_entities(&text).into_owned()
+}
+
+/// Renders Markdown as plain text, keeping the text of links and code but not their markup
+pub fn markdown_to_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::Html(html) => text.push_str(&html_to_text(&html)),
+            _ => text.push(' '),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        let test_cases = vec![
+            ("Great movie.<br /><br />Renu", "Great movie.  Renu"),
+            ("<p class=\"review\">Tom &amp; Jerry&#39;s <b>best</b></p>", " Tom & Jerry's  best  "),
+            ("rated 1 < 2 <3", "rated 1 < 2 <3"),
+            ("a<!-- hidden -->b", "a b"),
+            ("<script>var br = '<br>';</script>text<STYLE>p {}</style>", " text "),
+            ("unclosed <b", "unclosed <b"),
+        ];
+
+        for (html, expected) in test_cases {
+            assert_eq!(html_to_text(html), expected, "html `{}`", html);
+        }
+    }
+
+    #[test]
+    fn test_markdown_to_text() {
+        let text = markdown_to_text("# Kaalai\n\nA *life-time* [mistake](http://imdb.com).<br />\n\n`code`");
+        let words: Vec<&str> = text.split_whitespace().collect();
+        assert_eq!(words, vec!["Kaalai", "A", "life-time", "mistake", ".", "code"]);
+    }
+
+    #[test]
+    fn test_detect_content_type() {
+        let late_markup = format!("{}<br />", "a".repeat(SNIFF_LENGTH));
+        let test_cases = vec![
+            ("review.txt", "Great movie.<br /><br />Renu", ContentType::Html),
+            ("review.txt", "rated 1 < 2", ContentType::PlainText),
+            ("README.MD", "<br />", ContentType::Markdown),
+            ("page.htm", "no tags", ContentType::Html),
+            ("notes", "<I>italic</I>", ContentType::Html),
+            ("notes", "a </ b", ContentType::PlainText),
+            ("notes", &late_markup, ContentType::PlainText),
+        ];
+
+        for (path, content, expected) in test_cases {
+            assert_eq!(ContentType::detect(Path::new(path), content), expected, "path `{}`", path);
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::{collections::{HashMap, BTreeSet}, ops::{Bound, Deref}, sync::Arc};
+
+use crate::{inverted_index::{IndexReader, FieldReader, PostingReader, SortedTerms, QueryOptions, QueryResult, SearchResults, IndexStats, TermInfo, DocumentInfo}, analysis::Analyzer, schema::Schema, stemming::StemmerLanguage};
+
+/// A read-only index made with `InvertedIndex::freeze`, answering the same queries.
+///
+/// The terms of every field are kept in a sorted array and their posting lists in one flat array,
+/// and every other part of the index is plain data, so queries take no locks and copy no sets.
+/// Results are not cached
+#[derive(Debug)]
+pub struct FrozenIndex {
+    pub(crate) fields: HashMap<String, FrozenFieldIndex>,
+    pub(crate) documents: HashMap<Arc<String>, DocumentInfo>,
+    pub(crate) analyzer: Analyzer,
+    pub(crate) schema: Schema,
+    pub(crate) languages: BTreeSet<StemmerLanguage>,
+    pub(crate) metadata_keys: BTreeSet<String>,
+    pub(crate) synonyms: HashMap<Vec<String>, Vec<Vec<String>>>,
+}
+
+impl FrozenIndex {
+    pub fn query(&self, query: &str) -> Vec<QueryResult> {
+        self.query_with_options(query, &QueryOptions::default())
+    }
+
+    pub fn query_with_options(&self, query: &str, options: &QueryOptions) -> Vec<QueryResult> {
+        self.search(query, options).results
+    }
+
+    /// See `InvertedIndex::search`
+    pub fn search(&self, query: &str, options: &QueryOptions) -> SearchResults {
+        self.search_uncached(query, options)
+    }
+
+    /// See `InvertedIndex::more_like_this`
+    pub fn more_like_this(&self, document: &str, max_terms: usize, max_results: usize) -> Option<Vec<QueryResult>> {
+        IndexReader::more_like_this(self, document, max_terms, max_results)
+    }
+
+    /// See `InvertedIndex::suggest`
+    pub fn suggest(&self, query: &str, max_suggestions: usize) -> Vec<String> {
+        IndexReader::suggest(self, query, max_suggestions)
+    }
+
+    pub fn stats(&self, top: usize) -> IndexStats {
+        IndexReader::stats(self, top)
+    }
+
+    pub fn term_info(&self, word: &str) -> Vec<TermInfo> {
+        IndexReader::term_info(self, word)
+    }
+
+    pub fn document_info(&self, document: &String) -> DocumentInfo {
+        IndexReader::document_info(self, document)
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn analyzer(&self) -> &Analyzer {
+        &self.analyzer
+    }
+}
+
+impl IndexReader for FrozenIndex {
+    type Field = FrozenFieldIndex;
+
+    fn analyzer(&self) -> &Analyzer {
+        &self.analyzer
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn synonyms(&self) -> &HashMap<Vec<String>, Vec<Vec<String>>> {
+        &self.synonyms
+    }
+
+    fn metadata_keys(&self) -> impl Deref<Target = BTreeSet<String>> {
+        &self.metadata_keys
+    }
+
+    fn languages(&self) -> impl Deref<Target = BTreeSet<StemmerLanguage>> {
+        &self.languages
+    }
+
+    fn field(&self, name: &str) -> Option<&FrozenFieldIndex> {
+        self.fields.get(name)
+    }
+
+    fn fields(&self) -> impl Iterator<Item = (&String, &FrozenFieldIndex)> {
+        self.fields.iter()
+    }
+
+    fn document(&self, document: &String) -> Option<impl Deref<Target = DocumentInfo>> {
+        self.documents.get(document)
+    }
+
+    fn document_count(&self) -> usize {
+        self.documents.len()
+    }
+
+    fn for_each_document(&self, mut f: impl FnMut(&Arc<String>, &DocumentInfo)) {
+        for (document, info) in &self.documents {
+            f(document, info);
+        }
+    }
+}
+
+/// Postings of a single field in contiguous arrays
+#[derive(Debug, Default)]
+pub(crate) struct FrozenFieldIndex {
+    /// Sorted
+    terms: Vec<String>,
+    /// Surface form of every term in `terms`
+    surfaces: Vec<String>,
+    /// Documents containing `terms[i]` are `documents[offsets[i]..offsets[i + 1]]`
+    offsets: Vec<usize>,
+    /// Posting lists of all terms one after another, each sorted by path
+    documents: Vec<Arc<String>>,
+}
+
+/// A posting list of a `FrozenFieldIndex`
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FrozenPosting<'a> {
+    /// Sorted by path
+    pub documents: &'a [Arc<String>],
+    pub surface: &'a str,
+}
+
+impl FrozenFieldIndex {
+    /// Builds the arrays from every term of a field along with its surface form and documents, in any order
+    pub(crate) fn new(postings: impl IntoIterator<Item = (String, String, Vec<Arc<String>>)>) -> Self {
+        let mut postings: Vec<_> = postings.into_iter().collect();
+        postings.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut index = Self {
+            terms: Vec::with_capacity(postings.len()),
+            surfaces: Vec::with_capacity(postings.len()),
+            offsets: Vec::with_capacity(postings.len() + 1),
+            documents: Vec::with_capacity(postings.iter().map(|p| p.2.len()).sum()),
+        };
+        index.offsets.push(0);
+        for (term, surface, mut documents) in postings {
+            documents.sort();
+            index.terms.push(term);
+            index.surfaces.push(surface);
+            index.documents.extend(documents);
+            index.offsets.push(index.documents.len());
+        }
+        index
+    }
+
+    /// The posting list of the term at `position` in the sorted terms
+    fn posting_at(&self, position: usize) -> FrozenPosting<'_> {
+        FrozenPosting {
+            documents: &self.documents[self.offsets[position]..self.offsets[position + 1]],
+            surface: &self.surfaces[position],
+        }
+    }
+
+    /// Terms between `from` and `to`
+    fn range(&self, from: Bound<&str>, to: Bound<&str>) -> &[String] {
+        let start = match from {
+            Bound::Included(from) => self.terms.partition_point(|t| t.as_str() < from),
+            Bound::Excluded(from) => self.terms.partition_point(|t| t.as_str() <= from),
+            Bound::Unbounded => 0,
+        };
+        let end = match to {
+            Bound::Included(to) => self.terms.partition_point(|t| t.as_str() <= to),
+            Bound::Excluded(to) => self.terms.partition_point(|t| t.as_str() < to),
+            Bound::Unbounded => self.terms.len(),
+        };
+        &self.terms[start..end.max(start)]
+    }
+}
+
+impl FieldReader for FrozenFieldIndex {
+    type Posting<'a> = FrozenPosting<'a>;
+
+    fn posting(&self, term: &str) -> Option<FrozenPosting<'_>> {
+        self.terms.binary_search_by(|t| t.as_str().cmp(term))
+            .ok()
+            .map(|position| self.posting_at(position))
+    }
+
+    fn terms(&self) -> impl SortedTerms {
+        self
+    }
+
+    fn for_each_posting<'a>(&'a self, mut f: impl FnMut(&String, &FrozenPosting<'a>)) {
+        for (position, term) in self.terms.iter().enumerate() {
+            f(term, &self.posting_at(position));
+        }
+    }
+
+    fn memory_estimate(&self) -> usize {
+        self.terms.iter().chain(&self.surfaces).map(|s| size_of::<String>() + s.capacity()).sum::<usize>()
+            + self.offsets.capacity() * size_of::<usize>()
+            + self.documents.capacity() * size_of::<Arc<String>>()
+    }
+}
+
+impl PostingReader for FrozenPosting<'_> {
+    fn documents(&self) -> impl Iterator<Item = &Arc<String>> {
+        self.documents.iter()
+    }
+
+    fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    fn contains(&self, document: &String) -> bool {
+        self.documents.binary_search_by(|d| d.as_str().cmp(document)).is_ok()
+    }
+
+    fn surface(&self) -> &str {
+        self.surface
+    }
+}
+
+impl SortedTerms for &FrozenFieldIndex {
+    fn range(&self, from: Bound<&str>, to: Bound<&str>) -> impl Iterator<Item = &String> {
+        FrozenFieldIndex::range(self, from, to).iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{word_filtering::scan_for_unique_words, inverted_index::InvertedIndex};
+
+    use super::*;
+
+    fn postings(documents: &[&str]) -> Vec<Arc<String>> {
+        documents.iter().map(|d| Arc::new(d.to_string())).collect()
+    }
+
+    #[test]
+    fn test_frozen_field_index() {
+        let index = FrozenFieldIndex::new(vec![
+            ("movi".to_owned(), "movie".to_owned(), postings(&["c", "a"])),
+            ("cat".to_owned(), "cats".to_owned(), postings(&["b"])),
+            ("dog".to_owned(), "dog".to_owned(), postings(&["a", "b", "c"])),
+        ]);
+
+        assert_eq!(index.range(Bound::Unbounded, Bound::Unbounded), ["cat", "dog", "movi"]);
+        let movie = index.posting("movi").unwrap();
+        assert_eq!((movie.surface, movie.documents), ("movie", &postings(&["a", "c"])[..]));
+        assert_eq!(index.posting("dog").unwrap().documents, &postings(&["a", "b", "c"])[..]);
+        assert!(index.posting("bird").is_none());
+
+        assert_eq!(index.range(Bound::Included("d"), Bound::Unbounded), ["dog", "movi"]);
+        assert_eq!(index.range(Bound::Included("cat"), Bound::Included("dog")), ["cat", "dog"]);
+        assert_eq!(index.range(Bound::Excluded("cat"), Bound::Excluded("movi")), ["dog"]);
+        assert!(index.range(Bound::Included("z"), Bound::Included("a")).is_empty());
+    }
+
+    #[test]
+    fn test_frozen_index_matches_inverted_index() {
+        let documents = vec![
+            ("a.txt", "a movie about movies and cats"),
+            ("b.txt", "another movie, with a horse"),
+            ("c.txt", "a bad move for a house"),
+            ("d.txt", "cats and dogs"),
+        ];
+        let index_with = || {
+            let index = InvertedIndex::new();
+            for (name, content) in &documents {
+                index.insert(name.to_string(), scan_for_unique_words(content).unwrap());
+            }
+            index
+        };
+        let index = index_with();
+        let frozen = index_with().freeze();
+        let results = |results: Vec<QueryResult>| results.into_iter()
+            .map(|r| (r.document, r.rank))
+            .collect::<Vec<_>>();
+
+        let queries = ["movie", "cats dogs", "mov*", "bad..cat", "hors~", "text:house", "ext:txt", "nothing"];
+        let fuzzy = QueryOptions { fuzzy: true, ..Default::default() };
+        for query in queries {
+            assert_eq!(results(frozen.query(query)), results(index.query(query)), "query `{}`", query);
+            assert_eq!(results(frozen.query_with_options(query, &fuzzy)), results(index.query_with_options(query, &fuzzy)),
+                "fuzzy query `{}`", query);
+        }
+        assert!(!frozen.query("movie").is_empty());
+        assert_eq!(frozen.suggest("movei hourse", 3), index.suggest("movei hourse", 3));
+        assert_eq!(results(frozen.more_like_this("a.txt", 25, 10).unwrap()), results(index.more_like_this("a.txt", 25, 10).unwrap()));
+        assert!(frozen.more_like_this("unknown", 25, 10).is_none());
+        assert_eq!(frozen.term_info("movies"), index.term_info("movies"));
+
+        let (frozen_stats, stats) = (frozen.stats(3), index.stats(3));
+        assert_eq!((frozen_stats.documents, frozen_stats.terms, frozen_stats.postings),
+            (stats.documents, stats.terms, stats.postings));
+        assert_eq!(frozen_stats.most_frequent_terms, stats.most_frequent_terms);
+        assert_eq!(frozen_stats.longest_postings, stats.longest_postings);
+        assert!(frozen_stats.memory_estimate > 0);
+    }
+}
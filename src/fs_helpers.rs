@@ -1,43 +1,221 @@
-use std::{fs::File, path::{PathBuf, Path}, collections::HashSet, thread, sync::Arc};
+use std::{fs::{self, File}, fmt, io::{self, Read}, path::{PathBuf, Path}, thread, sync::Arc, time::SystemTime, collections::BTreeMap};
 
-use log::error;
+use log::{error, info, warn};
+use serde::Serialize;
+use serde_json::Value;
 
-use crate::{word_filtering::reader_to_words, inverted_index::InvertedIndex};
+use crate::{archives::{self, ArchiveKind}, inverted_index::{InvertedIndex, DocumentInfo}, extraction::ContentType, decoding::{decode, is_utf8_candidate, sniff_binary, BinaryContent, SNIFF_LENGTH, DecodeWarning, InvalidUtf8}, records::{parse_records, RecordFormat, RecordOptions, RECORD_SEPARATOR}};
 
-pub fn insert_files_into_inverted_index(files: Arc<Vec<PathBuf>>, inverted_index: &Arc<InvertedIndex>, thread_count: usize) {
+/// Settings for reading files into an index
+#[derive(Debug, Clone, Default)]
+pub struct IngestOptions {
+    pub invalid_utf8: InvalidUtf8,
+    /// How documents are made of records in JSON Lines and CSV files
+    pub records: RecordOptions,
+}
+
+/// What happened to the files given to `insert_files_into_inverted_index`.
+/// Every member of an archive is counted as a document of its own
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IngestionReport {
+    /// Number of documents inserted into the index, including the ones with warnings
+    pub indexed: usize,
+    pub warnings: Vec<(PathBuf, DecodeWarning)>,
+    /// Documents that were not indexed on purpose, like binaries
+    pub skipped: Vec<(PathBuf, BinaryContent)>,
+    /// Files and documents that could not be read, with the error
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl IngestionReport {
+    fn merge(&mut self, other: Self) {
+        self.indexed += other.indexed;
+        self.warnings.extend(other.warnings);
+        self.skipped.extend(other.skipped);
+        self.failed.extend(other.failed);
+    }
+}
+
+impl fmt::Display for IngestionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} documents indexed, {} with decode warnings, {} skipped as binary, {} failed",
+            self.indexed, self.warnings.len(), self.skipped.len(), self.failed.len())
+    }
+}
+
+pub fn insert_files_into_inverted_index(
+    files: Arc<Vec<PathBuf>>,
+    inverted_index: &Arc<InvertedIndex>,
+    thread_count: usize,
+    options: &IngestOptions,
+) -> IngestionReport {
     let mut threads = Vec::with_capacity(thread_count);
-    let segment_size = files.len()/thread_count;
+    let segment_size = files.len().div_ceil(thread_count);
     let segments = (0..thread_count)
-        .map(|i| i*segment_size..(i+1)*segment_size);
+        .map(|i| (i*segment_size).min(files.len())..((i+1)*segment_size).min(files.len()));
     for segment in segments {
         let inverted_index = Arc::clone(inverted_index);
         let files = Arc::clone(&files);
+        let options = options.clone();
         threads.push(thread::spawn(move||{
+            let mut report = IngestionReport::default();
             for file_path in &files[segment] {
-                match unique_words_in_file(&file_path) {
-                    Ok(words) => {
-                        let s_path = (*file_path.to_string_lossy()).to_owned();
-                        inverted_index.insert(s_path, words);
-                    },
-                    Err(err) => error!("Error reading unique words in {:?};
-                    error: {}", file_path, err),
+                let modified = file_path.metadata().and_then(|m| m.modified()).ok();
+                let mut insert = |document: String, bytes: Vec<u8>| {
+                    insert_document(document, &bytes, modified, &inverted_index, &options, &mut report);
+                };
+
+                let result = match ArchiveKind::from_path(file_path) {
+                    Some(kind) => archives::for_each_member(file_path, kind, &mut insert),
+                    None => insert_file(file_path, modified, &inverted_index, &options, &mut report),
+                };
+                if let Err(err) = result {
+                    error!("Error reading {:?}; error: {}", file_path, err);
+                    report.failed.push((file_path.clone(), err.to_string()));
                 }
             }
+            report
         }));
     }
+
+    let mut report = IngestionReport::default();
     for thread in threads {
-        thread.join().unwrap();
+        report.merge(thread.join().unwrap());
+    }
+    report
+}
+
+/// Inserts a regular file into the index. Plain UTF-8 text is tokenized while it is read, and the whole file
+/// is only read into memory when it has to be decoded, have its text extracted or stored, or be split into records
+fn insert_file(
+    path: &Path,
+    modified: Option<SystemTime>,
+    inverted_index: &InvertedIndex,
+    options: &IngestOptions,
+    report: &mut IngestionReport,
+) -> io::Result<()> {
+    let document = path.to_string_lossy().into_owned();
+    let mut file = File::open(path)?;
+    let mut bytes = Vec::with_capacity(SNIFF_LENGTH);
+    (&mut file).take(SNIFF_LENGTH as u64).read_to_end(&mut bytes)?;
+
+    let binary = sniff_binary(&bytes).is_some();
+    let schema = inverted_index.schema();
+    let plain_text = !binary
+        && !schema.field(&schema.default_field).is_some_and(|field| field.stored)
+        && is_utf8_candidate(&bytes)
+        && RecordFormat::from_path(path).is_none()
+        && ContentType::detect(path, &String::from_utf8_lossy(&bytes)) == ContentType::PlainText;
+    if plain_text {
+        match inverted_index.analyzer().tokenize_reader(bytes.as_slice().chain(&mut file)) {
+            Ok(words) => {
+                let info = file_info(path, file.metadata()?.len() as usize, modified);
+                inverted_index.insert_with_info(document, words, info);
+                report.indexed += 1;
+                return Ok(());
+            },
+            // not UTF-8 after all, so it is read again and decoded like any other file
+            Err(err) if err.kind() == io::ErrorKind::InvalidInput => {
+                bytes = fs::read(path)?;
+            },
+            Err(err) => return Err(err),
+        }
+    } else if !binary {
+        file.read_to_end(&mut bytes)?;
+    }
+    // a binary file is skipped by its first bytes, so the rest is never read
+    insert_document(document, &bytes, modified, inverted_index, options, report);
+    Ok(())
+}
+
+/// Tokenizes the text of a document, with markup like HTML tags extracted away first, and inserts it into the index.
+/// A record file is split into records, each inserted as a document of its own. Binary documents are skipped
+fn insert_document(
+    document: String,
+    bytes: &[u8],
+    modified: Option<SystemTime>,
+    inverted_index: &InvertedIndex,
+    options: &IngestOptions,
+    report: &mut IngestionReport,
+) {
+    let document_path = PathBuf::from(&document);
+    if let Some(reason) = sniff_binary(bytes) {
+        info!("skipping {:?}: {}", document_path, reason);
+        report.skipped.push((document_path, reason));
+        return;
+    }
+    let (content, warning) = match decode(bytes, options.invalid_utf8) {
+        Ok(decoded) => decoded,
+        Err(err) => {
+            error!("Error reading unique words in {:?};
+                    error: {}", document_path, err);
+            report.failed.push((document_path, err.to_string()));
+            return;
+        },
+    };
+    if let Some(warning) = warning {
+        warn!("{:?}: {}", document_path, warning);
+        report.warnings.push((document_path.clone(), warning));
+    }
+
+    let Some(format) = RecordFormat::from_path(&document_path) else {
+        let text = ContentType::detect(&document_path, &content).extract_text(&content).into_owned();
+        let info = file_info(&document_path, bytes.len(), modified);
+        let fields = BTreeMap::from([(inverted_index.schema().default_field.clone(), text)]);
+        inverted_index.insert_fields(document, fields, info);
+        report.indexed += 1;
+        return;
+    };
+
+    for record in parse_records(&content, format, &options.records) {
+        match record {
+            Ok(mut record) => {
+                // the text goes into the default field, and record fields named like other fields of the schema into those
+                let schema = inverted_index.schema();
+                let mut fields = BTreeMap::from([(schema.default_field.clone(), record.text)]);
+                for field in schema.fields.iter().filter(|f| f.name != schema.default_field) {
+                    if let Some(value) = record.metadata.remove(&field.name) {
+                        let value = match value {
+                            Value::String(value) => value,
+                            value => value.to_string(),
+                        };
+                        fields.insert(field.name.clone(), value);
+                    }
+                }
+                for text in fields.values_mut() {
+                    *text = ContentType::sniff(text).extract_text(text).into_owned();
+                }
+
+                let info = DocumentInfo {
+                    metadata: record.metadata,
+                    ..file_info(&document_path, record.size, modified)
+                };
+                let record_path = format!("{}{}{}", document, RECORD_SEPARATOR, record.id);
+                inverted_index.insert_fields(record_path, fields, info);
+                report.indexed += 1;
+            },
+            Err(err) => {
+                error!("Error reading a record in {:?}; error: {}", document_path, err);
+                report.failed.push((document_path.clone(), err.to_string()));
+            },
+        }
     }
 }
 
-fn unique_words_in_file(file_path: &PathBuf) -> std::io::Result<HashSet<String>> {
-    let file_handle = File::open(file_path)?;
-    reader_to_words(file_handle)
+/// Attributes of a document that can be told from its path
+fn file_info(path: &Path, size: usize, modified: Option<SystemTime>) -> DocumentInfo {
+    DocumentInfo {
+        modified,
+        size: Some(size as u64),
+        extension: path.extension().map(|extension| extension.to_string_lossy().to_lowercase()),
+        directory: path.parent().map(|directory| directory.to_string_lossy().into_owned()),
+        ..Default::default()
+    }
 }
 
 pub fn get_file_paths_from_directories<'a>(directory_paths: impl Iterator<Item = &'a String>) -> Vec<PathBuf> {
     directory_paths
-        .map(|s| Path::new(s))
+        .map(Path::new)
             .filter(|p| {
                 if !p.exists() {
                     error!("{:?} does not exist", p);
@@ -59,10 +237,76 @@ pub fn get_file_paths_from_directories<'a>(directory_paths: impl Iterator<Item =
                             return Some(file_path);
                         }
                     }
-                    return None
+                    None
                 }))
             } else {
                 Box::new(std::iter::empty())
             }
         }).collect()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{analysis::Analyzer, schema::Schema};
+
+    use super::*;
+
+    #[test]
+    fn test_ingestion_report() {
+        let directory = std::env::temp_dir().join(format!("parallel_computing_test_ingestion_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("utf8.txt"), "Café au lait").unwrap();
+        fs::write(directory.join("latin1.txt"), b"Caf\xe9 noir").unwrap();
+        fs::write(directory.join("review.txt"), "Great movie<br /><br />").unwrap();
+        fs::write(directory.join("reviews.jsonl"), "{\"id\": \"tt1\", \"text\": \"Café<br />crème\", \"rating\": 9}\n{\"rating\": 1}\n").unwrap();
+        fs::write(directory.join("long.txt"), format!("{}sequel", "word ".repeat(SNIFF_LENGTH))).unwrap();
+        fs::write(directory.join("poster.png"), b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR").unwrap();
+
+        let directories = [directory.to_string_lossy().into_owned()];
+        let files = Arc::new(get_file_paths_from_directories(directories.iter()));
+        let index = Arc::new(InvertedIndex::new());
+        let options = IngestOptions {
+            records: RecordOptions { id_field: Some("id".to_owned()), ..Default::default() },
+            ..Default::default()
+        };
+        let report = insert_files_into_inverted_index(Arc::clone(&files), &index, 2, &options);
+        let strict_options = IngestOptions { invalid_utf8: InvalidUtf8::Fail, ..Default::default() };
+        let strict_report = insert_files_into_inverted_index(files, &Arc::new(InvertedIndex::new()), 3, &strict_options);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(report.indexed, 5);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.skipped, vec![(directory.join("poster.png"), BinaryContent::Format { name: "PNG image" })]);
+        assert_eq!(report.warnings, vec![(directory.join("latin1.txt"), DecodeWarning::Fallback { encoding: "windows-1252" })]);
+        assert_eq!(index.query("café").len(), 3);
+        let record = format!("{}#tt1", directory.join("reviews.jsonl").to_string_lossy());
+        assert_eq!(index.query("crème")[0].document, record);
+        assert_eq!(index.document_info(&record).metadata["rating"], 9);
+        assert!(index.query("br").is_empty());
+        assert_eq!(index.query("sequel").len(), 1);
+        assert_eq!(index.query("café ext:jsonl")[0].document, record);
+        assert_eq!(index.query("café size<12").len(), 1);
+        assert_eq!(index.query("rating>5").len(), 1);
+        let directory_name = directory.file_name().unwrap().to_string_lossy();
+        assert_eq!(index.query(&format!("dir:{} ext:txt", directory_name)).len(), 4);
+        assert_eq!((strict_report.indexed, strict_report.failed.len()), (4, 2));
+    }
+
+    #[test]
+    fn test_stored_default_field() {
+        let directory = std::env::temp_dir().join(format!("parallel_computing_test_stored_{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("plain.txt"), "Great movie").unwrap();
+        fs::write(directory.join("review.html"), "<p>Awful movie</p>").unwrap();
+
+        let schema: Schema = serde_yaml::from_str("fields: [{name: text, stored: true}]").unwrap();
+        let index = Arc::new(InvertedIndex::with_schema(Analyzer::default(), schema));
+        let files = Arc::new(get_file_paths_from_directories([directory.to_string_lossy().into_owned()].iter()));
+        insert_files_into_inverted_index(files, &index, 1, &IngestOptions::default());
+        fs::remove_dir_all(&directory).unwrap();
+
+        let mut stored: Vec<String> = index.query("movie").into_iter().map(|r| r.fields["text"].clone()).collect();
+        stored.sort();
+        assert_eq!(stored, vec![" Awful movie ", "Great movie"]);
+    }
+}
@@ -1,72 +1,1726 @@
-use std::{collections::{HashSet, HashMap}, sync::Arc};
+use std::{collections::{HashSet, HashMap, BTreeSet, BTreeMap}, sync::{Arc, RwLock, RwLockReadGuard, atomic::{AtomicU64, Ordering}}, time::SystemTime, cell::Cell, ops::{Bound, Deref}};
 
-use chashmap::CHashMap;
+use chashmap::{CHashMap, ReadGuard};
 use log::debug;
 use serde::{Serialize, Deserialize};
+use serde_json::Value;
 
-use crate::{word_filtering::scan_for_unique_words};
+use crate::{analysis::Analyzer, schema::{Schema, Field}, stemming::StemmerLanguage, query::{parse_query, QueryClause, Filter, literal_prefix, wildcard_matches, bounded_edit_distance}, query_cache::{QueryCache, QueryCacheConfig, QueryCacheStats}, frozen_index::{FrozenIndex, FrozenFieldIndex}};
 
 #[derive(Debug)]
 pub struct InvertedIndex {
-    hashmap: CHashMap<String, HashSet<Arc<String>>>
+    /// Postings of every indexed field of `schema`
+    fields: HashMap<String, FieldIndex>,
+    documents: CHashMap<Arc<String>, DocumentInfo>,
+    /// Every key of `documents` in insertion order. CHashMap can only be iterated by taking every bucket's write lock
+    document_list: RwLock<Vec<Arc<String>>>,
+    analyzer: Analyzer,
+    schema: Schema,
+    /// Stemmer languages documents were inserted with
+    languages: RwLock<BTreeSet<StemmerLanguage>>,
+    /// Keys of the metadata of every document, which queries can filter on
+    metadata_keys: RwLock<BTreeSet<String>>,
+    /// Phrases of the analyzer's synonym rules, as query terms, mapped to the phrases they expand into
+    synonyms: HashMap<Vec<String>, Vec<Vec<String>>>,
+    /// Incremented whenever documents change, so cached results of earlier generations are not used
+    generation: AtomicU64,
+    query_cache: QueryCache,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Stems of a single field
+#[derive(Debug, Default)]
+pub(crate) struct FieldIndex {
+    hashmap: CHashMap<String, Posting>,
+    /// Every stem in `hashmap` in sorted order, used to expand wildcard and range queries
+    terms: RwLock<BTreeSet<String>>,
+}
+
+/// Documents containing a stem
+#[derive(Debug, Clone)]
+pub(crate) struct Posting {
+    documents: HashSet<Arc<String>>,
+    /// The shortest lowercase word seen with this stem, used to present the stem to a user
+    surface: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct QueryResult {
     pub document: String,
-    pub rank: usize,
+    pub rank: f64,
+    /// Text of the document's stored fields
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+    /// How every clause of the query contributed to `rank`, only filled in with the `explain` option
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub explanation: Vec<ClauseExplanation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClauseExplanation {
+    /// The clause as written in the query
+    pub clause: String,
+    /// Terms the clause was analyzed or expanded into, in any field
+    pub terms: Vec<String>,
+    /// Terms found in the document, at most one per field and query word
+    pub matches: Vec<TermMatch>,
+    /// What the clause added to the rank, the sum of `weight * boost` over `matches`
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TermMatch {
+    pub field: String,
+    pub term: String,
+    /// 1 for exact matches, less for fuzzy matches and synonyms
+    pub weight: f64,
+    /// Boost of the field
+    pub boost: f64,
+}
+
+/// A document's rank for a single clause along with the terms it matched,
+/// which are only collected with the `explain` option
+#[derive(Debug, Default)]
+pub(crate) struct ClauseMatch {
+    rank: f64,
+    terms: Vec<TermMatch>,
+}
+
+/// What is known about a document besides the words in it
+#[derive(Debug, Clone, Default)]
+pub struct DocumentInfo {
+    /// Number of unique words in the document
+    pub length: usize,
+    pub modified: Option<SystemTime>,
+    /// Size in bytes of the file or archive member, or of the line or row of a record
+    pub size: Option<u64>,
+    /// Lowercase extension of the file, without the dot
+    pub extension: Option<String>,
+    /// Directory the file is in
+    pub directory: Option<String>,
+    /// Language the document was stemmed in, `None` if the analyzer has no stemmer
+    pub language: Option<StemmerLanguage>,
+    /// Fields of a record besides its ID and text, empty for documents that are whole files
+    pub metadata: BTreeMap<String, Value>,
+    /// Text of the fields the schema declares as stored
+    pub stored: BTreeMap<String, String>,
+    /// Terms of the fields the schema keeps term vectors of
+    pub terms: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    /// Highest rank first
+    #[default]
+    Rank,
+    /// Alphabetical by document path
+    Path,
+    /// Most recently modified first
+    Modified,
+    /// Shortest document first
+    Length,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueryOptions {
+    pub sort_by: SortBy,
+    /// Reverses the resulting order
+    pub reverse: bool,
+    /// Maximum number of terms a single wildcard, range or fuzzy clause expands into
+    pub max_expansions: usize,
+    /// Matches every word in the query as if it was written as `word~`
+    pub fuzzy: bool,
+    /// Maximum edit distance for fuzzy clauses that do not specify one
+    pub fuzzy_distance: usize,
+    /// Number of leading characters a fuzzy match has to share with the query word
+    pub fuzzy_prefix_length: usize,
+    /// Maximum number of corrected queries proposed when nothing is found
+    pub max_suggestions: usize,
+    /// Looks up stopwords instead of dropping them from the query.
+    /// Only useful with stopword filters that still index stopwords
+    pub keep_stopwords: bool,
+    /// Stems query words in this language.
+    /// By default the analyzer's stemmer is used, or every language in the index if it detects languages
+    pub language: Option<StemmerLanguage>,
+    /// Attributes to count the values of among all matching documents, like `ext` or a metadata key
+    pub facets: Vec<String>,
+    /// Maximum number of values returned per facet, most frequent first
+    pub max_facet_values: usize,
+    /// Explains the rank of every result
+    pub explain: bool,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            sort_by: SortBy::default(),
+            reverse: false,
+            max_expansions: 64,
+            fuzzy: false,
+            fuzzy_distance: 2,
+            fuzzy_prefix_length: 1,
+            max_suggestions: 3,
+            keep_stopwords: false,
+            language: None,
+            facets: vec![],
+            max_facet_values: 10,
+            explain: false,
+        }
+    }
+}
+
+/// Results of a query along with the facets requested in its options
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchResults {
+    pub results: Vec<QueryResult>,
+    /// Requested attributes mapped to their most frequent values among the results
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub facets: BTreeMap<String, Vec<FacetCount>>,
+}
+
+/// Number of matching documents with a value of an attribute
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: usize,
+}
+
+/// Size of the index and its most common terms
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndexStats {
+    pub documents: usize,
+    /// Distinct terms, counting a term once for every field it is in
+    pub terms: usize,
+    /// Sum of the lengths of all posting lists
+    pub postings: usize,
+    /// Approximate number of bytes taken by terms, postings and documents
+    pub memory_estimate: usize,
+    /// Terms with the most postings over all fields, counting a document once for every field it has the term in
+    pub most_frequent_terms: Vec<TermStats>,
+    /// Longest posting lists of single fields
+    pub longest_postings: Vec<TermStats>,
+    pub query_cache: QueryCacheStats,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TermStats {
+    /// Field of the posting list, `None` when counted over all fields
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub term: String,
+    /// Shortest word the term was produced from
+    pub surface: String,
+    pub postings: usize,
+}
+
+/// A term of a field along with the documents containing it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TermInfo {
+    pub field: String,
+    pub term: String,
+    pub surface: String,
+    pub document_frequency: usize,
+    /// Sorted by path
+    pub documents: Vec<String>,
 }
 
 impl InvertedIndex {
     pub fn insert(&self, document: String, words: HashSet<String>) {
-        let stems = Self::words_to_stems(words);
+        self.insert_with_info(document, words, DocumentInfo::default())
+    }
+
+    /// Inserts a document, recording `info` alongside it. `words` go into the schema's default field.
+    /// `info.length` and `info.language` are overwritten with the number of unique words in `words`
+    /// and the language they were stemmed in.
+    /// Only words are given, so nothing is stored even if the default field is; `insert_fields` takes the text to store
+    pub fn insert_with_info(&self, document: String, words: HashSet<String>, info: DocumentInfo) {
+        let fields = HashMap::from([(self.schema.default_field.clone(), words)]);
+        self.insert_words(document, fields, info);
+    }
+
+    /// Inserts a document made of named fields, mapped to their text.
+    /// Indexed fields are tokenized with the analyzer, stored fields are kept in `info.stored`,
+    /// fields missing from the schema are ignored
+    pub fn insert_fields(&self, document: String, fields: BTreeMap<String, String>, mut info: DocumentInfo) {
+        let mut words = HashMap::new();
+        for (name, text) in fields {
+            let Some(field) = self.schema.field(&name) else {
+                continue;
+            };
+            if field.indexed {
+                words.insert(name.clone(), self.analyzer.tokenize_str(&text));
+            }
+            if field.stored {
+                info.stored.insert(name, text);
+            }
+        }
+        self.insert_words(document, words, info);
+    }
+
+    fn insert_words(&self, document: String, fields: HashMap<String, HashSet<String>>, mut info: DocumentInfo) {
+        let all_words: HashSet<String> = fields.values().flatten().cloned().collect();
+        info.length = all_words.len();
+        info.language = self.analyzer.document_language(&all_words);
+        if let Some(language) = info.language {
+            if !self.languages.read().unwrap().contains(&language) {
+                self.languages.write().unwrap().insert(language);
+            }
+        }
+        let new_keys: Vec<&String> = {
+            let metadata_keys = self.metadata_keys.read().unwrap();
+            info.metadata.keys().filter(|key| !metadata_keys.contains(*key)).collect()
+        };
+        if !new_keys.is_empty() {
+            self.metadata_keys.write().unwrap().extend(new_keys.into_iter().cloned());
+        }
         let document = Arc::new(document);
 
-        let insert = ||
-            vec![Arc::clone(&document)].into_iter().collect();
-        let update = |old: &mut HashSet<Arc<String>>| {
-            old.insert(Arc::clone(&document)); };
-        
-        for stem in stems {
-            self.hashmap.upsert(stem, &insert, update);
+        for (name, words) in fields {
+            let Some(field_index) = self.fields.get(&name) else {
+                continue;
+            };
+            let stems = self.analyzer.analyze_tokens(words, info.language);
+            if self.schema.field(&name).is_some_and(|field| field.term_vectors) {
+                let mut terms: Vec<String> = stems.keys().cloned().collect();
+                terms.sort();
+                info.terms.insert(name, terms);
+            }
+            field_index.insert(&document, stems);
         }
+        if self.documents.insert(Arc::clone(&document), info).is_none() {
+            self.document_list.write().unwrap().push(document);
+        }
+        self.generation.fetch_add(1, Ordering::Release);
     }
 
     pub fn query(&self, query: &str) -> Vec<QueryResult> {
-        debug!("processing inverse_index query `{}`", query);
-        let words = match scan_for_unique_words(query) {
-            Some(w) => w,
-            None => return vec![],
+        self.query_with_options(query, &QueryOptions::default())
+    }
+
+    pub fn query_with_options(&self, query: &str, options: &QueryOptions) -> Vec<QueryResult> {
+        Arc::unwrap_or_clone(self.search(query, options)).results
+    }
+
+    /// Results with an equal sort key are ordered by rank, then by document path,
+    /// so the same query always yields the same order.
+    ///
+    /// Only documents passing every filter clause are returned.
+    /// A query made only of filters returns every document passing them, with a rank of 0.
+    /// Facets are counted over all results while their stored fields are looked up.
+    /// Results are cached until documents are inserted and shared with the cache rather than copied
+    pub fn search(&self, query: &str, options: &QueryOptions) -> Arc<SearchResults> {
+        let key = QueryCache::key(query, options);
+        let generation = self.generation.load(Ordering::Acquire);
+        if let Some(results) = self.query_cache.get(&key, generation) {
+            debug!("query `{}` found in the cache", query);
+            return results;
+        }
+        let results = Arc::new(IndexReader::search_uncached(self, query, options));
+        self.query_cache.insert(key, generation, Arc::clone(&results));
+        results
+    }
+
+    /// Documents sharing the most distinctive terms of `document`, most similar first, without `document` itself.
+    ///
+    /// Documents are indexed as sets of stems, so every term of `document` occurs in it once
+    /// and its TF-IDF weight is its inverse document frequency `ln(N / df)`. The `max_terms` heaviest terms
+    /// found in other documents are looked up like query words, each match adding the term's weight
+    /// multiplied by the boost of its field. At most `max_results` documents are returned.
+    /// The terms of `document` are read from its term vectors, in fields without them every posting list is searched.
+    /// Returns `None` if `document` is not in the index
+    pub fn more_like_this(&self, document: &str, max_terms: usize, max_results: usize) -> Option<Vec<QueryResult>> {
+        IndexReader::more_like_this(self, document, max_terms, max_results)
+    }
+
+    /// Proposes up to `max_suggestions` corrected versions of `query`.
+    ///
+    /// Words whose stem is not in the index are replaced with the closest known terms,
+    /// preferring smaller edit distances, then terms that occur in more documents.
+    /// Only whitespace-separated chunks made of nothing but a misspelled word are replaced, as a whole
+    pub fn suggest(&self, query: &str, max_suggestions: usize) -> Vec<String> {
+        IndexReader::suggest(self, query, max_suggestions)
+    }
+
+    /// Counts documents, terms and postings, and lists the `top` most frequent terms and longest posting lists
+    pub fn stats(&self, top: usize) -> IndexStats {
+        IndexStats { query_cache: self.query_cache.stats(), ..IndexReader::stats(self, top) }
+    }
+
+    /// Looks up the terms `word` is analyzed into in every field, stopwords included
+    pub fn term_info(&self, word: &str) -> Vec<TermInfo> {
+        IndexReader::term_info(self, word)
+    }
+
+    pub fn document_info(&self, document: &String) -> DocumentInfo {
+        IndexReader::document_info(self, document)
+    }
+
+    pub fn new() -> Self {
+        Self::with_analyzer(Analyzer::default())
+    }
+
+    pub fn with_analyzer(analyzer: Analyzer) -> Self {
+        Self::with_schema(analyzer, Schema::default())
+    }
+
+    pub fn with_schema(analyzer: Analyzer, schema: Schema) -> Self {
+        let mut synonyms = HashMap::<Vec<String>, Vec<Vec<String>>>::new();
+        for rule in analyzer.synonyms.rules() {
+            let phrase: Option<Vec<String>> = rule.phrase.iter()
+                .map(|w| analyzer.analyze_query_term(w, true, None))
+                .collect();
+            if let Some(phrase) = phrase {
+                let expansions = synonyms.entry(phrase).or_default();
+                for synonym in &rule.synonyms {
+                    if !expansions.contains(synonym) {
+                        expansions.push(synonym.clone());
+                    }
+                }
+            }
+        }
+
+        Self {
+            fields: schema.indexed_fields()
+                .map(|f| (f.name.clone(), FieldIndex::default()))
+                .collect(),
+            documents: CHashMap::new(),
+            document_list: RwLock::new(vec![]),
+            analyzer,
+            schema,
+            languages: RwLock::new(BTreeSet::new()),
+            metadata_keys: RwLock::new(BTreeSet::new()),
+            synonyms,
+            generation: AtomicU64::new(0),
+            query_cache: QueryCache::default(),
+        }
+    }
+
+    /// Replaces the default query cache with one limited by `config`
+    pub fn with_query_cache(mut self, config: QueryCacheConfig) -> Self {
+        self.query_cache = QueryCache::new(config);
+        self
+    }
+
+    /// Converts the index into a read-only `FrozenIndex` once every document is inserted,
+    /// moving the postings of every field into sorted arrays. The query cache is dropped
+    pub fn freeze(self) -> FrozenIndex {
+        FrozenIndex {
+            fields: self.fields.into_iter()
+                .map(|(name, field_index)| {
+                    let postings = field_index.hashmap.into_iter()
+                        .map(|(term, posting)| (term, posting.surface, posting.documents.into_iter().collect()));
+                    (name, FrozenFieldIndex::new(postings))
+                })
+                .collect(),
+            documents: self.documents.into_iter().collect(),
+            analyzer: self.analyzer,
+            schema: self.schema,
+            languages: self.languages.into_inner().unwrap(),
+            metadata_keys: self.metadata_keys.into_inner().unwrap(),
+            synonyms: self.synonyms,
+        }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// The analyzer documents and queries are run through.
+    /// Words passed to `insert` should come from its tokenizer
+    pub fn analyzer(&self) -> &Analyzer {
+        &self.analyzer
+    }
+}
+
+/// Read access to the fields and documents of an index.
+/// Queries are answered by the provided methods, shared by `InvertedIndex` and `FrozenIndex`
+pub(crate) trait IndexReader {
+    type Field: FieldReader;
+
+    fn analyzer(&self) -> &Analyzer;
+
+    fn schema(&self) -> &Schema;
+
+    /// Phrases of the analyzer's synonym rules, as query terms, mapped to the phrases they expand into
+    fn synonyms(&self) -> &HashMap<Vec<String>, Vec<Vec<String>>>;
+
+    /// Keys of the metadata of every document, which queries can filter on
+    fn metadata_keys(&self) -> impl Deref<Target = BTreeSet<String>>;
+
+    /// Stemmer languages documents were inserted with
+    fn languages(&self) -> impl Deref<Target = BTreeSet<StemmerLanguage>>;
+
+    fn field(&self, name: &str) -> Option<&Self::Field>;
+
+    /// Every indexed field along with its name
+    fn fields(&self) -> impl Iterator<Item = (&String, &Self::Field)>;
+
+    // documents are keyed by `Arc<String>`, which can only be borrowed as a `String`
+    #[allow(clippy::ptr_arg)]
+    fn document(&self, document: &String) -> Option<impl Deref<Target = DocumentInfo>>;
+
+    fn document_count(&self) -> usize;
+
+    fn for_each_document(&self, f: impl FnMut(&Arc<String>, &DocumentInfo));
+
+    fn search_uncached(&self, query: &str, options: &QueryOptions) -> SearchResults {
+        debug!("processing inverse_index query `{}` with {:?}", query, options);
+        let (filters, clauses): (Vec<QueryClause>, Vec<QueryClause>) = self.expand_synonyms(self.parse(query))
+            .into_iter()
+            .partition(|clause| matches!(clause, QueryClause::Filter(_)));
+        let filters: Vec<Filter> = filters.into_iter()
+            .filter_map(|clause| match clause {
+                QueryClause::Filter(filter) => Some(filter),
+                _ => None,
+            })
+            .collect();
+        debug!("clauses found in `{}`: {:?}, filters: {:?}", query, clauses, filters);
+
+        let mut seen_stems = HashSet::new();
+        let clause_matches: Vec<(&QueryClause, HashMap<Arc<String>, ClauseMatch>)> = clauses.iter()
+            .filter(|clause| match clause {
+                QueryClause::Word(w) => {
+                    let stems = self.query_terms(w, options.keep_stopwords, options.language);
+                    !stems.is_empty() && seen_stems.insert(stems)
+                },
+                _ => true,
+            })
+            .map(|clause| (clause, self.clause_documents(clause, options)))
+            .collect();
+
+        let mut v: Vec<(Arc<String>, f64)> = if clauses.is_empty() && !filters.is_empty() {
+            self.filtered_documents(&filters).into_iter()
+                .map(|document| (document, 0.0))
+                .collect()
+        } else {
+            let mut ranks = HashMap::<Arc<String>, f64>::new();
+            for (_, documents) in &clause_matches {
+                for (document, clause_match) in documents {
+                    *ranks.entry(Arc::clone(document)).or_insert(0.0) += clause_match.rank;
+                }
+            }
+            ranks.into_iter()
+                .filter(|(document, _)| filters.is_empty() || self.document(document)
+                    .is_some_and(|info| filters.iter().all(|filter| filter.matches(&info))))
+                .collect()
+        };
+
+        let by_rank = |a: &(Arc<String>, f64), b: &(Arc<String>, f64)|
+            b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0));
+        match options.sort_by {
+            SortBy::Rank => v.sort_by(by_rank),
+            SortBy::Path => v.sort_by(|a, b| a.0.cmp(&b.0)),
+            SortBy::Modified | SortBy::Length => {
+                let mut keyed: Vec<_> = v.into_iter()
+                    .map(|item| {
+                        let key = self.document(&item.0).map(|info| (info.modified, info.length));
+                        (key.unwrap_or_default(), item)
+                    })
+                    .collect();
+                keyed.sort_by(|((a_modified, a_length), a), ((b_modified, b_length), b)| {
+                    let ordering = match options.sort_by {
+                        SortBy::Modified => b_modified.cmp(a_modified),
+                        _ => a_length.cmp(b_length),
+                    };
+                    ordering.then_with(|| by_rank(a, b))
+                });
+                v = keyed.into_iter().map(|(_, item)| item).collect();
+            },
+        }
+        if options.reverse {
+            v.reverse();
+        }
+
+        let clause_terms: Vec<Vec<String>> = match options.explain {
+            true => clause_matches.iter().map(|(clause, _)| self.clause_terms(clause, options)).collect(),
+            false => vec![],
         };
-        debug!("words found in `{}`: {:?}", query, words);
-        let stems = Self::words_to_stems(words);
-        debug!("stems found in `{}`: {:?}", query, stems);
-
-        let mut v: Vec<QueryResult> = stems.into_iter()
-            .filter_map(|s| {
-                self.hashmap.get(&s)
-                    .and_then(|v| Some((*v).clone()))
-            }).fold(HashMap::<String, usize>::new(), |mut accum, item| {
-                for stem in item {
-                    accum.entry(stem.to_string())
-                        .and_modify(|count| *count += 1)
-                        .or_insert(1);
+        let mut facet_counts: Vec<HashMap<String, usize>> = vec![HashMap::new(); options.facets.len()];
+        let results = v.into_iter()
+            .map(|(document, rank)| {
+                let explanation = clause_terms.iter()
+                    .zip(&clause_matches)
+                    .map(|(terms, (clause, documents))| {
+                        let clause_match = documents.get(&document);
+                        ClauseExplanation {
+                            clause: clause.to_string(),
+                            terms: terms.clone(),
+                            score: clause_match.map_or(0.0, |m| m.rank),
+                            matches: clause_match.map(|m| m.terms.clone()).unwrap_or_default(),
+                        }
+                    })
+                    .collect();
+                let mut result = QueryResult {
+                    document: document.to_string(),
+                    rank,
+                    fields: BTreeMap::new(),
+                    explanation,
+                };
+                if let Some(info) = self.document(&document) {
+                    for (attribute, counts) in options.facets.iter().zip(facet_counts.iter_mut()) {
+                        for value in info.facet_values(attribute) {
+                            *counts.entry(value).or_insert(0) += 1;
+                        }
+                    }
+                    result.fields = info.stored.clone();
                 }
-                accum
-            }).into_iter().map(|(key, rank)| QueryResult{document: key, rank})
+                result
+            })
+            .collect();
+
+        let facets = options.facets.iter()
+            .zip(facet_counts)
+            .map(|(attribute, counts)| {
+                let mut counts: Vec<FacetCount> = counts.into_iter()
+                    .map(|(value, count)| FacetCount { value, count })
+                    .collect();
+                counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+                counts.truncate(options.max_facet_values);
+                (attribute.clone(), counts)
+            })
             .collect();
-        v.sort_by(|a, b| b.rank.partial_cmp(&a.rank).unwrap());
-        v
+        SearchResults { results, facets }
     }
 
-    pub fn new() -> Self {
-        Self { hashmap: CHashMap::new() }
+    fn parse(&self, query: &str) -> Vec<QueryClause> {
+        parse_query(query, self.analyzer(), self.schema(), &self.metadata_keys())
+    }
+
+    /// Replaces runs of `Word` clauses that make up the phrase of a synonym rule with a `Synonyms` clause.
+    /// Phrases are compared by their query terms, so `films` matches a rule for `film`, and the longest one wins
+    fn expand_synonyms(&self, clauses: Vec<QueryClause>) -> Vec<QueryClause> {
+        let max_length = self.synonyms().keys().map(Vec::len).max().unwrap_or(0);
+        if max_length == 0 {
+            return clauses;
+        }
+
+        let mut expanded = Vec::with_capacity(clauses.len());
+        let mut i = 0;
+        while i < clauses.len() {
+            let words: Vec<&String> = clauses[i..].iter()
+                .take(max_length)
+                .map_while(|clause| match clause {
+                    QueryClause::Word(w) => Some(w),
+                    _ => None,
+                })
+                .collect();
+            let rule = (1..=words.len()).rev().find_map(|length| {
+                let phrase: Option<Vec<String>> = words[..length].iter()
+                    .map(|w| self.analyzer().analyze_query_term(w, true, None))
+                    .collect();
+                self.synonyms().get(&phrase?).map(|synonyms| (length, synonyms))
+            });
+            match rule {
+                Some((length, synonyms)) => {
+                    expanded.push(QueryClause::Synonyms {
+                        words: words[..length].iter().map(|w| w.to_string()).collect(),
+                        synonyms: synonyms.clone(),
+                    });
+                    i += length;
+                },
+                None => {
+                    expanded.push(clauses[i].clone());
+                    i += 1;
+                },
+            }
+        }
+        expanded
+    }
+
+    /// Every document passing all of `filters`
+    fn filtered_documents(&self, filters: &[Filter]) -> Vec<Arc<String>> {
+        let mut matching = vec![];
+        self.for_each_document(|document, info| {
+            if filters.iter().all(|filter| filter.matches(info)) {
+                matching.push(Arc::clone(document));
+            }
+        });
+        matching
     }
 
-    fn words_to_stems(words: HashSet<String>) -> HashSet<String> {
-        words.into_iter()
-            .map(|w| w.to_lowercase())
-            .map(|w| porter_stemmer::stem(&w))
+    /// Documents containing any of the terms `clause` expands into, each weighted by the best matching term.
+    /// Unless `clause` is restricted to a field, the weights in every indexed field are multiplied
+    /// by the field's boost and summed
+    fn clause_documents(&self, clause: &QueryClause, options: &QueryOptions) -> HashMap<Arc<String>, ClauseMatch> {
+        if let QueryClause::Synonyms { words, synonyms } = clause {
+            return self.synonym_documents(words, synonyms, options);
+        }
+        let (fields, clause): (Vec<&Field>, &QueryClause) = match clause {
+            QueryClause::Field { field, clause } => (self.schema().field(field).into_iter().collect(), clause),
+            clause => (self.schema().indexed_fields().collect(), clause),
+        };
+
+        let mut documents = HashMap::<Arc<String>, ClauseMatch>::new();
+        for field in fields {
+            let Some(field_index) = self.field(&field.name) else {
+                continue;
+            };
+            let terms = self.expand_clause(field_index, clause, options);
+            debug!("{:?} expanded into {:?} in field {}", clause, terms, field.name);
+
+            // the best weight of every document along with the term it comes from
+            let mut field_documents = HashMap::new();
+            for (i, (term, weight)) in terms.iter().enumerate() {
+                if let Some(posting) = field_index.posting(term) {
+                    for document in posting.documents() {
+                        let best = field_documents.entry(Arc::clone(document)).or_insert((*weight, i));
+                        if *weight > best.0 {
+                            *best = (*weight, i);
+                        }
+                    }
+                }
+            }
+            for (document, (weight, i)) in field_documents {
+                let clause_match = documents.entry(document).or_default();
+                clause_match.rank += field.boost * weight;
+                if options.explain {
+                    clause_match.terms.push(TermMatch {
+                        field: field.name.clone(),
+                        term: terms[i].0.clone(),
+                        weight,
+                        boost: field.boost,
+                    });
+                }
+            }
+        }
+        documents
+    }
+
+    /// Documents containing any of `words` or every word of one of their `synonyms`.
+    ///
+    /// A document's rank is the sum of the ranks of the words it contains, or if it is higher,
+    /// the synonym weight times the number of `words` for a synonym it contains,
+    /// scaled by the mean rank of the synonym's words
+    fn synonym_documents(&self, words: &[String], synonyms: &[Vec<String>], options: &QueryOptions) -> HashMap<Arc<String>, ClauseMatch> {
+        // stopwords that are dropped from queries can't be required
+        let word_documents = |words: &[String]| -> Vec<HashMap<Arc<String>, ClauseMatch>> {
+            words.iter()
+                .filter(|w| !self.query_terms(w, options.keep_stopwords, options.language).is_empty())
+                .map(|w| self.clause_documents(&QueryClause::Word(w.clone()), options))
+                .collect()
+        };
+
+        let mut documents = HashMap::<Arc<String>, ClauseMatch>::new();
+        for (document, word_match) in word_documents(words).into_iter().flatten() {
+            let clause_match = documents.entry(document).or_default();
+            clause_match.rank += word_match.rank;
+            clause_match.terms.extend(word_match.terms);
+        }
+        let weight = self.analyzer().synonyms.weight() * words.len() as f64;
+        for synonym in synonyms {
+            let synonym_documents = word_documents(synonym);
+            let Some((first, _)) = synonym_documents.split_first() else {
+                continue;
+            };
+            let scale = weight / synonym_documents.len() as f64;
+            for document in first.keys() {
+                let word_matches: Option<Vec<&ClauseMatch>> = synonym_documents.iter()
+                    .map(|d| d.get(document))
+                    .collect();
+                let Some(word_matches) = word_matches else {
+                    continue;
+                };
+                let rank = scale * word_matches.iter().map(|m| m.rank).sum::<f64>();
+                let best = documents.entry(Arc::clone(document)).or_default();
+                if rank > best.rank {
+                    best.rank = rank;
+                    best.terms = word_matches.iter()
+                        .flat_map(|m| &m.terms)
+                        .map(|t| TermMatch { weight: scale * t.weight, ..t.clone() })
+                        .collect();
+                }
+            }
+        }
+        documents
+    }
+
+    /// Terms `clause` is analyzed or expanded into in any field, for explaining ranks
+    fn clause_terms(&self, clause: &QueryClause, options: &QueryOptions) -> Vec<String> {
+        let mut terms: Vec<String> = match clause {
+            QueryClause::Synonyms { words, synonyms } => words.iter()
+                .chain(synonyms.iter().flatten())
+                .flat_map(|w| self.query_terms(w, options.keep_stopwords, options.language))
+                .collect(),
+            QueryClause::Field { field, clause } => self.field(field)
+                .map(|field_index| self.expand_clause(field_index, clause, options))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(term, _)| term)
+                .collect(),
+            clause => self.fields()
+                .flat_map(|(_, field_index)| self.expand_clause(field_index, clause, options))
+                .map(|(term, _)| term)
+                .collect(),
+        };
+        terms.sort();
+        terms.dedup();
+        terms
+    }
+
+    /// Terms of a field `clause` matches along with their weights.
+    /// Exact terms weigh 1, fuzzy matches weigh `1 / (1 + distance)`
+    fn expand_clause(&self, field_index: &Self::Field, clause: &QueryClause, options: &QueryOptions) -> Vec<(String, f64)> {
+        let terms = field_index.terms();
+        let max_expansions = options.max_expansions;
+        let exact = |t: &String| (t.clone(), 1.0);
+        match clause {
+            QueryClause::Word(w) if options.fuzzy =>
+                self.expand_fuzzy(&terms, w, options.fuzzy_distance, options),
+            QueryClause::Word(w) => self.query_terms(w, options.keep_stopwords, options.language)
+                .into_iter()
+                .map(|stem| (stem, 1.0))
+                .collect(),
+            QueryClause::Wildcard(pattern) => {
+                let pattern = self.analyzer().normalize(pattern);
+                let prefix = literal_prefix(&pattern);
+                terms.range(Bound::Included(prefix), Bound::Unbounded)
+                    .take_while(|t| t.starts_with(prefix))
+                    .filter(|t| wildcard_matches(&pattern, t))
+                    .take(max_expansions)
+                    .map(exact)
+                    .collect()
+            },
+            QueryClause::Range { from, to } => {
+                // endpoints are analyzed like words, stopwords included, so `apple..banana` starts at the stem `appl`.
+                // With several stemmer languages the range spans from the lowest stem to the highest
+                let stems = |word: &str| match self.query_terms(word, true, options.language) {
+                    stems if stems.is_empty() => vec![self.analyzer().normalize(word)],
+                    stems => stems,
+                };
+                let (Some(from), Some(to)) = (stems(from).into_iter().min(), stems(to).into_iter().max()) else {
+                    return vec![];
+                };
+                if from > to {
+                    return vec![];
+                }
+                terms.range(Bound::Included(from.as_str()), Bound::Included(to.as_str()))
+                    .take(max_expansions)
+                    .map(exact)
+                    .collect()
+            },
+            QueryClause::Fuzzy { word, max_distance } => self.expand_fuzzy(&terms, word,
+                max_distance.unwrap_or(options.fuzzy_distance), options),
+            // the parser never nests field clauses, synonyms are matched word by word and filters don't match terms
+            QueryClause::Field { .. } | QueryClause::Synonyms { .. } | QueryClause::Filter(_) => vec![],
+        }
+    }
+
+    /// Terms within `max_distance` edits of the stems of `word`, closest first
+    fn expand_fuzzy(&self, terms: &impl SortedTerms, word: &str, max_distance: usize, options: &QueryOptions) -> Vec<(String, f64)> {
+        let mut matches = HashMap::<String, usize>::new();
+        for stem in self.query_terms(word, options.keep_stopwords, options.language) {
+            let prefix: String = stem.chars().take(options.fuzzy_prefix_length).collect();
+            let stem_matches = terms
+                .range(Bound::Included(prefix.as_str()), Bound::Unbounded)
+                .take_while(|t| t.starts_with(&prefix))
+                .filter_map(|t| bounded_edit_distance(&stem, t, max_distance)
+                    .map(|distance| (t.clone(), distance)));
+            for (term, distance) in stem_matches {
+                let best = matches.entry(term).or_insert(distance);
+                *best = distance.min(*best);
+            }
+        }
+
+        let mut matches: Vec<(String, usize)> = matches.into_iter().collect();
+        matches.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        matches.into_iter()
+            .take(options.max_expansions)
+            .map(|(term, distance)| (term, 1.0 / (1 + distance) as f64))
             .collect()
     }
-}
\ No newline at end of file
+
+    fn more_like_this(&self, document: &str, max_terms: usize, max_results: usize) -> Option<Vec<QueryResult>> {
+        let document = document.to_owned();
+        let document_terms = self.document(&document)?.terms.clone();
+        let document_count = self.document_count() as f64;
+
+        let mut terms: Vec<(&Field, &Self::Field, String, f64)> = vec![];
+        for field in self.schema().indexed_fields() {
+            let Some(field_index) = self.field(&field.name) else {
+                continue;
+            };
+            let mut weigh = |term: &String, document_frequency: usize| {
+                // terms no other document has can't find similar ones, and terms every document has weigh 0
+                if document_frequency > 1 {
+                    let weight = (document_count / document_frequency as f64).ln();
+                    if weight > 0.0 {
+                        terms.push((field, field_index, term.clone(), weight));
+                    }
+                }
+            };
+            match field.term_vectors {
+                true => for term in document_terms.get(&field.name).into_iter().flatten() {
+                    weigh(term, field_index.posting(term).map_or(0, |posting| posting.len()));
+                },
+                false => field_index.for_each_posting(|term, posting| {
+                    if posting.contains(&document) {
+                        weigh(term, posting.len());
+                    }
+                }),
+            }
+        }
+        terms.sort_by(|a, b| b.3.total_cmp(&a.3).then_with(|| a.2.cmp(&b.2)));
+        terms.truncate(max_terms);
+        debug!("terms like {}: {:?}", document, terms.iter().map(|t| (&t.2, t.3)).collect::<Vec<_>>());
+
+        let mut ranks = HashMap::<Arc<String>, f64>::new();
+        for (field, field_index, term, weight) in terms {
+            if let Some(posting) = field_index.posting(&term) {
+                for other in posting.documents().filter(|other| ***other != document) {
+                    *ranks.entry(Arc::clone(other)).or_insert(0.0) += field.boost * weight;
+                }
+            }
+        }
+
+        let mut ranks: Vec<(Arc<String>, f64)> = ranks.into_iter().collect();
+        ranks.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let results = ranks.into_iter()
+            .take(max_results)
+            .map(|(document, rank)| {
+                let fields = self.document(&document)
+                    .map(|info| info.stored.clone())
+                    .unwrap_or_default();
+                QueryResult { document: document.to_string(), rank, fields, explanation: vec![] }
+            })
+            .collect();
+        Some(results)
+    }
+
+    fn suggest(&self, query: &str, max_suggestions: usize) -> Vec<String> {
+        let mut corrections: Vec<(String, Vec<String>)> = vec![];
+        for clause in self.parse(query) {
+            let word = match clause {
+                QueryClause::Word(w) => w,
+                _ => continue,
+            };
+            let stems = self.query_terms(&word, false, None);
+            let stem = match stems.first() {
+                Some(stem) => stem,
+                None => continue,
+            };
+            if stems.iter().any(|stem| self.fields().any(|(_, f)| f.posting(stem).is_some())) {
+                continue;
+            }
+            let candidates = self.spelling_candidates(stem, max_suggestions);
+            if !candidates.is_empty() {
+                corrections.push((word, candidates));
+            }
+        }
+        if corrections.is_empty() {
+            return vec![];
+        }
+
+        let suggestion_count = corrections.iter()
+            .map(|(_, candidates)| candidates.len())
+            .max().unwrap();
+        // chunks mapped to the candidates replacing them, only chunks that parse into a single misspelled word are replaced
+        let chunks: Vec<(&str, Option<&Vec<String>>)> = query.split_whitespace()
+            .map(|chunk| match self.parse(chunk).as_slice() {
+                [QueryClause::Word(w)] => (chunk, corrections.iter()
+                    .find(|(word, _)| word == w)
+                    .map(|(_, candidates)| candidates)),
+                _ => (chunk, None),
+            })
+            .collect();
+        let mut suggestions: Vec<String> = vec![];
+        for i in 0..suggestion_count {
+            let suggestion = chunks.iter()
+                .map(|(chunk, candidates)| match candidates {
+                    Some(candidates) => candidates.get(i).unwrap_or(&candidates[0]).as_str(),
+                    None => chunk,
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+        }
+        debug!("suggestions for `{}`: {:?}", query, suggestions);
+        suggestions
+    }
+
+    /// Surface forms of the terms closest to `stem` in any field, best first
+    fn spelling_candidates(&self, stem: &str, max_candidates: usize) -> Vec<String> {
+        const MAX_DISTANCE: usize = 2;
+
+        // surface form to the distance and the most documents it occurs in within a field
+        let mut candidates = HashMap::<String, (usize, usize)>::new();
+        for (_, field_index) in self.fields() {
+            field_index.for_each_posting(|term, posting| {
+                if let Some(distance) = bounded_edit_distance(stem, term, MAX_DISTANCE) {
+                    let candidate = candidates.entry(posting.surface().to_owned()).or_insert((distance, 0));
+                    candidate.1 = candidate.1.max(posting.len());
+                }
+            });
+        }
+
+        let mut candidates: Vec<(usize, usize, String)> = candidates.into_iter()
+            .map(|(surface, (distance, document_count))| (distance, document_count, surface))
+            .collect();
+        candidates.sort_by(|a, b| a.0.cmp(&b.0)
+            .then_with(|| b.1.cmp(&a.1))
+            .then_with(|| a.2.cmp(&b.2)));
+        candidates.into_iter()
+            .take(max_candidates)
+            .map(|(_, _, surface)| surface)
+            .collect()
+    }
+
+    /// Terms a query `word` is looked up by.
+    /// Without an explicit `language` and with a language-detecting analyzer,
+    /// `word` is stemmed in every language present in the index
+    fn query_terms(&self, word: &str, keep_stopwords: bool, language: Option<StemmerLanguage>) -> Vec<String> {
+        let languages: Vec<Option<StemmerLanguage>> = match (language, self.analyzer().stemmer_language()) {
+            (Some(language), _) => vec![Some(language)],
+            (None, Some(StemmerLanguage::Auto)) => {
+                let languages = self.languages();
+                match languages.is_empty() {
+                    true => vec![None],
+                    false => languages.iter().map(|&l| Some(l)).collect(),
+                }
+            },
+            (None, _) => vec![None],
+        };
+
+        let mut terms = vec![];
+        for language in languages {
+            if let Some(term) = self.analyzer().analyze_query_term(word, keep_stopwords, language) {
+                if !terms.contains(&term) {
+                    terms.push(term);
+                }
+            }
+        }
+        terms
+    }
+
+    fn stats(&self, top: usize) -> IndexStats {
+        let mut stats = IndexStats { documents: self.document_count(), ..Default::default() };
+        let mut occurrences = HashMap::<String, (String, usize)>::new();
+        let mut longest_postings = vec![];
+        for (name, field_index) in self.fields() {
+            stats.memory_estimate += field_index.memory_estimate();
+            field_index.for_each_posting(|term, posting| {
+                let postings = posting.len();
+                stats.terms += 1;
+                stats.postings += postings;
+
+                let (surface, total) = occurrences.entry(term.clone())
+                    .or_insert_with(|| (posting.surface().to_owned(), 0));
+                *total += postings;
+                if posting.surface() < surface.as_str() {
+                    *surface = posting.surface().to_owned();
+                }
+                longest_postings.push(TermStats {
+                    field: Some(name.clone()),
+                    term: term.clone(),
+                    surface: posting.surface().to_owned(),
+                    postings,
+                });
+            });
+        }
+        self.for_each_document(|document, info| {
+            stats.memory_estimate += size_of::<(Arc<String>, DocumentInfo)>() + 2 * size_of::<usize>()
+                + size_of::<String>() + document.capacity()
+                + info.extension.as_ref().map_or(0, String::capacity)
+                + info.directory.as_ref().map_or(0, String::capacity)
+                + info.stored.iter().map(|(name, text)| name.len() + text.len()).sum::<usize>()
+                + info.metadata.iter().map(|(key, value)| key.len() + value.to_string().len()).sum::<usize>()
+                + info.terms.iter()
+                    .map(|(name, terms)| name.len() + terms.iter().map(|t| size_of::<String>() + t.capacity()).sum::<usize>())
+                    .sum::<usize>();
+        });
+
+        let by_postings = |a: &TermStats, b: &TermStats| b.postings.cmp(&a.postings)
+            .then_with(|| a.term.cmp(&b.term))
+            .then_with(|| a.field.cmp(&b.field));
+        let mut most_frequent_terms: Vec<TermStats> = occurrences.into_iter()
+            .map(|(term, (surface, postings))| TermStats { field: None, term, surface, postings })
+            .collect();
+        most_frequent_terms.sort_by(by_postings);
+        most_frequent_terms.truncate(top);
+        longest_postings.sort_by(by_postings);
+        longest_postings.truncate(top);
+        IndexStats { most_frequent_terms, longest_postings, ..stats }
+    }
+
+    fn term_info(&self, word: &str) -> Vec<TermInfo> {
+        let mut infos = vec![];
+        for term in self.query_terms(word, true, None) {
+            for field in self.schema().indexed_fields() {
+                let Some(posting) = self.field(&field.name).and_then(|f| f.posting(&term)) else {
+                    continue;
+                };
+                let mut documents: Vec<String> = posting.documents().map(|d| d.to_string()).collect();
+                documents.sort();
+                infos.push(TermInfo {
+                    field: field.name.clone(),
+                    term: term.clone(),
+                    surface: posting.surface().to_owned(),
+                    document_frequency: documents.len(),
+                    documents,
+                });
+            }
+        }
+        infos
+    }
+
+    fn document_info(&self, document: &String) -> DocumentInfo {
+        self.document(document)
+            .map(|info| (*info).clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Postings of a single field
+pub(crate) trait FieldReader {
+    type Posting<'a>: PostingReader where Self: 'a;
+
+    fn posting(&self, term: &str) -> Option<Self::Posting<'_>>;
+
+    /// Every term of the field
+    fn terms(&self) -> impl SortedTerms;
+
+    /// Calls `f` with every term and its posting list in sorted order
+    fn for_each_posting<'a>(&'a self, f: impl FnMut(&String, &Self::Posting<'a>));
+
+    /// Approximate number of bytes taken by terms and postings
+    fn memory_estimate(&self) -> usize;
+}
+
+/// Documents containing a term
+pub(crate) trait PostingReader {
+    fn documents(&self) -> impl Iterator<Item = &Arc<String>>;
+
+    fn len(&self) -> usize;
+
+    #[allow(clippy::ptr_arg)]
+    fn contains(&self, document: &String) -> bool;
+
+    /// The shortest lowercase word seen with the term, used to present the term to a user
+    fn surface(&self) -> &str;
+}
+
+/// Terms of a field in sorted order
+pub(crate) trait SortedTerms {
+    fn range(&self, from: Bound<&str>, to: Bound<&str>) -> impl Iterator<Item = &String>;
+}
+
+impl IndexReader for InvertedIndex {
+    type Field = FieldIndex;
+
+    fn analyzer(&self) -> &Analyzer {
+        &self.analyzer
+    }
+
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn synonyms(&self) -> &HashMap<Vec<String>, Vec<Vec<String>>> {
+        &self.synonyms
+    }
+
+    fn metadata_keys(&self) -> impl Deref<Target = BTreeSet<String>> {
+        self.metadata_keys.read().unwrap()
+    }
+
+    fn languages(&self) -> impl Deref<Target = BTreeSet<StemmerLanguage>> {
+        self.languages.read().unwrap()
+    }
+
+    fn field(&self, name: &str) -> Option<&FieldIndex> {
+        self.fields.get(name)
+    }
+
+    fn fields(&self) -> impl Iterator<Item = (&String, &FieldIndex)> {
+        self.fields.iter()
+    }
+
+    fn document(&self, document: &String) -> Option<impl Deref<Target = DocumentInfo>> {
+        self.documents.get(document)
+    }
+
+    fn document_count(&self) -> usize {
+        self.documents.len()
+    }
+
+    fn for_each_document(&self, mut f: impl FnMut(&Arc<String>, &DocumentInfo)) {
+        for document in self.document_list.read().unwrap().iter() {
+            if let Some(info) = self.documents.get(document) {
+                f(document, &info);
+            }
+        }
+    }
+}
+
+impl FieldIndex {
+    /// Adds `document` to the postings of `stems`, mapped to their surface forms
+    fn insert(&self, document: &Arc<String>, stems: HashMap<String, String>) {
+        let mut new_stems = vec![];
+        for (stem, surface) in stems {
+            let is_new = Cell::new(false);
+            let insert = || {
+                is_new.set(true);
+                Posting {
+                    documents: vec![Arc::clone(document)].into_iter().collect(),
+                    surface: surface.clone(),
+                }
+            };
+            let update = |old: &mut Posting| {
+                old.documents.insert(Arc::clone(document));
+                if (surface.len(), &surface) < (old.surface.len(), &old.surface) {
+                    old.surface = surface.clone();
+                }
+            };
+            self.hashmap.upsert(stem.clone(), insert, update);
+            if is_new.get() {
+                new_stems.push(stem);
+            }
+        }
+        if !new_stems.is_empty() {
+            self.terms.write().unwrap().extend(new_stems);
+        }
+    }
+}
+
+impl FieldReader for FieldIndex {
+    type Posting<'a> = ReadGuard<'a, String, Posting>;
+
+    fn posting(&self, term: &str) -> Option<ReadGuard<'_, String, Posting>> {
+        self.hashmap.get(term)
+    }
+
+    fn terms(&self) -> impl SortedTerms {
+        self.terms.read().unwrap()
+    }
+
+    fn for_each_posting<'a>(&'a self, mut f: impl FnMut(&String, &ReadGuard<'a, String, Posting>)) {
+        for term in self.terms.read().unwrap().iter() {
+            if let Some(posting) = self.hashmap.get(term) {
+                f(term, &posting);
+            }
+        }
+    }
+
+    fn memory_estimate(&self) -> usize {
+        let mut memory_estimate = 0;
+        self.for_each_posting(|term, posting| {
+            memory_estimate += 2 * term.capacity() + posting.surface.capacity()
+                + size_of::<(String, Posting)>() + size_of::<String>()
+                + posting.documents.capacity() * (size_of::<Arc<String>>() + 1);
+        });
+        memory_estimate
+    }
+}
+
+impl PostingReader for ReadGuard<'_, String, Posting> {
+    fn documents(&self) -> impl Iterator<Item = &Arc<String>> {
+        self.documents.iter()
+    }
+
+    fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    fn contains(&self, document: &String) -> bool {
+        self.documents.contains(document)
+    }
+
+    fn surface(&self) -> &str {
+        &self.surface
+    }
+}
+
+impl SortedTerms for RwLockReadGuard<'_, BTreeSet<String>> {
+    fn range(&self, from: Bound<&str>, to: Bound<&str>) -> impl Iterator<Item = &String> {
+        BTreeSet::range::<str, _>(self, (from, to))
+    }
+}
+
+/// Ranges of sizes counted in a `size` facet along with the size they end before
+const SIZE_RANGES: [(&str, u64); 6] = [
+    ("0-1k", 1 << 10),
+    ("1k-10k", 10 << 10),
+    ("10k-100k", 100 << 10),
+    ("100k-1m", 1 << 20),
+    ("1m-10m", 10 << 20),
+    ("10m-100m", 100 << 20),
+];
+
+impl DocumentInfo {
+    /// Values of an attribute a document is counted under in a facet.
+    /// Modification times are counted per day and sizes in ranges like `1k-10k`,
+    /// every value of a metadata list is counted on its own
+    pub fn facet_values(&self, attribute: &str) -> Vec<String> {
+        match attribute {
+            "ext" => self.extension.iter().cloned().collect(),
+            "dir" => self.directory.iter().cloned().collect(),
+            "mtime" => self.modified.and_then(utc_day).into_iter().collect(),
+            "size" => self.size.iter()
+                .map(|&size| SIZE_RANGES.iter()
+                    .find(|&&(_, limit)| size < limit)
+                    .map_or("100m+", |&(name, _)| name)
+                    .to_owned())
+                .collect(),
+            key => match self.metadata.get(key) {
+                Some(Value::Array(values)) => values.iter().filter_map(metadata_facet_value).collect(),
+                Some(value) => metadata_facet_value(value).into_iter().collect(),
+                None => vec![],
+            },
+        }
+    }
+}
+
+/// The UTC date of `time` as `YYYY-MM-DD`, `None` for years outside 0 to 9999
+fn utc_day(time: SystemTime) -> Option<String> {
+    let seconds = match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(after) => i64::try_from(after.as_secs()).ok()?,
+        // a time before the epoch belongs to the day its fraction of a second is in, so round down
+        Err(before) => {
+            let before = before.duration();
+            let seconds = i64::try_from(before.as_secs()).ok()?;
+            -seconds - i64::from(before.subsec_nanos() > 0)
+        },
+    };
+
+    // days to a civil date, from Howard Hinnant's `civil_from_days`, with years starting in March
+    let days = seconds.div_euclid(86_400).checked_add(719_468)?;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (0..=9999).contains(&year).then(|| format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+fn metadata_facet_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Null | Value::Array(_) | Value::Object(_) => None,
+        Value::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+impl Default for InvertedIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use crate::word_filtering::scan_for_unique_words;
+
+    use super::*;
+
+    fn index_with(documents: Vec<(&str, &str, u64)>) -> InvertedIndex {
+        let index = InvertedIndex::new();
+        for (name, content, modified) in documents {
+            let words = scan_for_unique_words(content).unwrap();
+            let info = DocumentInfo {
+                modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified)),
+                ..Default::default()
+            };
+            index.insert_with_info(name.to_owned(), words, info);
+        }
+        index
+    }
+
+    fn documents(results: Vec<QueryResult>) -> Vec<String> {
+        results.into_iter().map(|r| r.document).collect()
+    }
+
+    #[test]
+    fn test_query_sort_order() {
+        let index = index_with(vec![
+            ("c.txt", "cats and dogs", 3),
+            ("a.txt", "cats are great, honestly", 1),
+            ("b.txt", "dogs", 2),
+            ("d.txt", "cats", 4),
+        ]);
+
+        struct SortTestCase {
+            options: QueryOptions,
+            expected: Vec<&'static str>,
+        }
+
+        let test_cases = vec![
+            SortTestCase {
+                options: QueryOptions::default(),
+                expected: vec!["c.txt", "a.txt", "b.txt", "d.txt"],
+            },
+            SortTestCase {
+                options: QueryOptions { sort_by: SortBy::Path, ..Default::default() },
+                expected: vec!["a.txt", "b.txt", "c.txt", "d.txt"],
+            },
+            SortTestCase {
+                options: QueryOptions { sort_by: SortBy::Modified, ..Default::default() },
+                expected: vec!["d.txt", "c.txt", "b.txt", "a.txt"],
+            },
+            SortTestCase {
+                options: QueryOptions { sort_by: SortBy::Length, ..Default::default() },
+                expected: vec!["b.txt", "d.txt", "c.txt", "a.txt"],
+            },
+            SortTestCase {
+                options: QueryOptions { reverse: true, ..Default::default() },
+                expected: vec!["d.txt", "b.txt", "a.txt", "c.txt"],
+            },
+        ];
+
+        for case in test_cases {
+            for _ in 0..5 {
+                let results = documents(index.query_with_options("cats dogs", &case.options));
+                assert_eq!(results, case.expected, "options: {:?}", case.options);
+            }
+        }
+    }
+
+    #[test]
+    fn test_query_expansions() {
+        let index = index_with(vec![
+            ("a.txt", "streams of colour", 0),
+            ("b.txt", "a streamer with a color", 0),
+            ("c.txt", "the stream", 0),
+            ("d.txt", "apples and bananas", 0),
+        ]);
+
+        let test_cases = vec![
+            ("stream*", QueryOptions::default(), vec!["a.txt", "b.txt", "c.txt"]),
+            ("col?r", QueryOptions::default(), vec!["b.txt"]),
+            ("col*r stream", QueryOptions::default(), vec!["a.txt", "b.txt", "c.txt"]),
+            ("apple..banana", QueryOptions::default(), vec!["d.txt"]),
+            ("apples..bananas", QueryOptions::default(), vec!["d.txt"]),
+            ("apple..apple", QueryOptions::default(), vec!["d.txt"]),
+            ("banana..apple", QueryOptions::default(), vec![]),
+            ("stream*", QueryOptions { max_expansions: 1, ..Default::default() }, vec!["a.txt", "c.txt"]),
+        ];
+
+        for (query, options, expected) in test_cases {
+            assert_eq!(documents(index.query_with_options(query, &options)), expected, "query `{}`", query);
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_query() {
+        let index = index_with(vec![
+            ("a.txt", "a movie about movies", 0),
+            ("b.txt", "the movei", 0),
+            ("c.txt", "some novel", 0),
+        ]);
+
+        let results = index.query("movei");
+        assert_eq!(documents(results), vec!["b.txt"]);
+
+        let results = index.query("movei~");
+        assert_eq!(results.iter().map(|r| (r.document.as_str(), r.rank)).collect::<Vec<_>>(),
+            vec![("b.txt", 1.0), ("a.txt", 0.5)]);
+
+        let fuzzy = QueryOptions { fuzzy: true, ..Default::default() };
+        assert_eq!(documents(index.query_with_options("movei", &fuzzy)), vec!["b.txt", "a.txt"]);
+
+        let no_prefix = QueryOptions { fuzzy: true, fuzzy_distance: 1, fuzzy_prefix_length: 0, ..Default::default() };
+        assert_eq!(documents(index.query_with_options("hovel", &fuzzy)), Vec::<String>::new());
+        assert_eq!(documents(index.query_with_options("hovel", &no_prefix)), vec!["c.txt"]);
+        assert_eq!(documents(index.query("movei~0")), vec!["b.txt"]);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let index = index_with(vec![
+            ("a.txt", "a movie about movies", 0),
+            ("b.txt", "another movie, with a horse", 0),
+            ("c.txt", "a bad move for a house", 0),
+        ]);
+
+        assert_eq!(index.suggest("good movei", 3), vec!["good movie", "good move"]);
+        assert_eq!(index.suggest("movei hourse", 1), vec!["movie horse"]);
+        assert_eq!(index.suggest("movie", 3), Vec::<String>::new());
+        assert_eq!(index.suggest("zzzzzzzz", 3), Vec::<String>::new());
+
+        let index = index_with(vec![("a.txt", "a cut in the catalog", 0)]);
+        assert_eq!(index.suggest("cat catalog", 1), vec!["cut catalog"]);
+        assert_eq!(index.suggest("catalog, cat!", 1), vec!["catalog, cut"]);
+    }
+
+    #[test]
+    fn test_multi_field_documents() {
+        let schema: Schema = serde_yaml::from_str("
+fields:
+  - name: title
+    boost: 2.0
+    stored: true
+  - name: text
+  - name: author
+    indexed: false
+    stored: true
+").unwrap();
+        let index = InvertedIndex::with_schema(Analyzer::default(), schema);
+        let documents_content = vec![
+            ("a", "Sinatra", "A movie about a singer", "Frank"),
+            ("b", "The captors", "Sinatra fans will hate this movie", "Renu"),
+            ("c", "Kaalai", "No story at all", "Simbu"),
+        ];
+        for (name, title, text, author) in documents_content {
+            let fields = BTreeMap::from([
+                ("title".to_owned(), title.to_owned()),
+                ("text".to_owned(), text.to_owned()),
+                ("author".to_owned(), author.to_owned()),
+                ("unknown".to_owned(), "ignored".to_owned()),
+            ]);
+            index.insert_fields(name.to_owned(), fields, DocumentInfo::default());
+        }
+
+        let results = index.query("sinatra");
+        assert_eq!(results.iter().map(|r| (r.document.as_str(), r.rank)).collect::<Vec<_>>(),
+            vec![("a", 2.0), ("b", 1.0)]);
+        assert_eq!(results[0].fields, BTreeMap::from([
+            ("author".to_owned(), "Frank".to_owned()),
+            ("title".to_owned(), "Sinatra".to_owned()),
+        ]));
+        assert_eq!(documents(index.query("title:sinatra")), vec!["a"]);
+        assert_eq!(documents(index.query("text:sinatra")), vec!["b"]);
+        assert_eq!(documents(index.query("title:cap* movie")), vec!["b", "a"]);
+        assert_eq!(documents(index.query("author:frank")), Vec::<String>::new());
+        assert_eq!(documents(index.query("ignored")), Vec::<String>::new());
+        assert_eq!(index.suggest("kaalia", 1), vec!["kaalai"]);
+
+        let explain = QueryOptions { explain: true, ..Default::default() };
+        let results = index.query_with_options("sinatra movei~", &explain);
+        let term_match = |field: &str, term: &str, weight, boost| TermMatch {
+            field: field.to_owned(),
+            term: term.to_owned(),
+            weight,
+            boost,
+        };
+        assert_eq!((results[0].document.as_str(), results[0].rank), ("a", 2.5));
+        assert_eq!(results[0].explanation, vec![
+            ClauseExplanation {
+                clause: "sinatra".to_owned(),
+                terms: vec!["sinatra".to_owned()],
+                matches: vec![term_match("title", "sinatra", 1.0, 2.0)],
+                score: 2.0,
+            },
+            ClauseExplanation {
+                clause: "movei~".to_owned(),
+                terms: vec!["movi".to_owned()],
+                matches: vec![term_match("text", "movi", 0.5, 1.0)],
+                score: 0.5,
+            },
+        ]);
+        assert!(index.query("sinatra")[0].explanation.is_empty());
+    }
+
+    #[test]
+    fn test_facets() {
+        let index = InvertedIndex::new();
+        let documents_content = vec![
+            ("train/pos/1.txt", "a great movie", "txt", "train/pos", 500, json!(["drama"])),
+            ("train/pos/2.html", "great acting", "html", "train/pos", 20_000, json!(["drama", "action"])),
+            ("train/neg/3.txt", "not a great movie", "txt", "train/neg", 1500, json!("comedy")),
+            ("train/neg/4.txt", "boring", "txt", "train/neg", 800, json!(null)),
+        ];
+        for (name, content, extension, directory, size, genres) in documents_content {
+            let info = DocumentInfo {
+                modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(86_400)),
+                size: Some(size),
+                extension: Some(extension.to_owned()),
+                directory: Some(directory.to_owned()),
+                metadata: BTreeMap::from([("genres".to_owned(), genres)]),
+                ..Default::default()
+            };
+            index.insert_with_info(name.to_owned(), index.analyzer().tokenize_str(content), info);
+        }
+
+        let count = |value: &str, count| FacetCount { value: value.to_owned(), count };
+        let options = QueryOptions {
+            facets: ["dir", "ext", "size", "mtime", "genres", "unknown"].map(String::from).to_vec(),
+            ..Default::default()
+        };
+        let results = index.search("great", &options);
+        assert_eq!(results.results.len(), 3);
+        assert_eq!(results.facets, BTreeMap::from([
+            ("dir".to_owned(), vec![count("train/pos", 2), count("train/neg", 1)]),
+            ("ext".to_owned(), vec![count("txt", 2), count("html", 1)]),
+            ("size".to_owned(), vec![count("0-1k", 1), count("10k-100k", 1), count("1k-10k", 1)]),
+            ("mtime".to_owned(), vec![count("1970-01-02", 3)]),
+            ("genres".to_owned(), vec![count("drama", 2), count("action", 1), count("comedy", 1)]),
+            ("unknown".to_owned(), vec![]),
+        ]));
+
+        let options = QueryOptions { facets: vec!["dir".to_owned()], max_facet_values: 1, ..Default::default() };
+        assert_eq!(index.search("ext:txt", &options).facets["dir"], vec![count("train/neg", 2)]);
+    }
+
+    #[test]
+    fn test_mtime_facet() {
+        let day = 86_400;
+        let mtime = |modified| DocumentInfo { modified: Some(modified), ..Default::default() }.facet_values("mtime");
+        let test_cases = vec![
+            (SystemTime::UNIX_EPOCH, vec!["1970-01-01"]),
+            (SystemTime::UNIX_EPOCH + Duration::from_secs(11_016 * day + day - 1), vec!["2000-02-29"]),
+            (SystemTime::UNIX_EPOCH - Duration::from_secs(365 * day), vec!["1969-01-01"]),
+            (SystemTime::UNIX_EPOCH - Duration::from_millis(1), vec!["1969-12-31"]),
+            (SystemTime::UNIX_EPOCH + Duration::from_secs(2_932_897 * day), vec![]),
+        ];
+        for (modified, expected) in test_cases {
+            assert_eq!(mtime(modified), expected, "time {:?}", modified);
+        }
+
+        let index = InvertedIndex::new();
+        let info = DocumentInfo { modified: Some(SystemTime::UNIX_EPOCH - Duration::from_secs(365 * day)), ..Default::default() };
+        index.insert_with_info("old.txt".to_owned(), index.analyzer().tokenize_str("old movie"), info);
+        let options = QueryOptions { facets: vec!["mtime".to_owned()], ..Default::default() };
+        assert_eq!(index.search("movie", &options).facets["mtime"], vec![FacetCount { value: "1969-01-01".to_owned(), count: 1 }]);
+    }
+
+    #[test]
+    fn test_more_like_this() {
+        let term_vectors: Schema = serde_yaml::from_str("fields: [{name: text, term_vectors: true}]").unwrap();
+        for schema in [Schema::default(), term_vectors] {
+            let keeps_terms = schema.fields[0].term_vectors;
+            let index = InvertedIndex::with_schema(Analyzer::default(), schema);
+            for (name, content) in [
+                ("a", "Sinatra, the singer Frank and his jazz"),
+                ("b", "Sinatra jazz records"),
+                ("c", "a jazz club"),
+                ("d", "pizza recipe"),
+                ("e", "pizza for Sinatra"),
+            ] {
+                index.insert(name.to_owned(), scan_for_unique_words(content).unwrap());
+            }
+            assert_eq!(index.document_info(&"a".to_owned()).terms.is_empty(), !keeps_terms);
+
+            let results = index.more_like_this("a", 25, 10).unwrap();
+            let weight = (5.0_f64 / 3.0).ln();
+            assert_eq!(results.iter().map(|r| (r.document.as_str(), r.rank)).collect::<Vec<_>>(),
+                vec![("b", 2.0 * weight), ("c", weight), ("e", weight)]);
+            assert_eq!(documents(index.more_like_this("a", 1, 10).unwrap()), vec!["b", "c"]);
+            assert_eq!(documents(index.more_like_this("a", 25, 2).unwrap()), vec!["b", "c"]);
+            assert_eq!(documents(index.more_like_this("d", 25, 10).unwrap()), vec!["e"]);
+            assert!(index.more_like_this("unknown", 25, 10).is_none());
+        }
+    }
+
+    #[test]
+    fn test_synonyms() {
+        let analyzer: Analyzer = serde_yaml::from_str("
+synonyms:
+  rules: ['film, movie', 'motion picture => movie', 'flick => film']
+").unwrap();
+        let index = InvertedIndex::with_analyzer(analyzer);
+        let documents_content = vec![
+            ("a", "a good film"),
+            ("b", "a good movie"),
+            ("c", "a motion picture"),
+            ("d", "a bad picture"),
+        ];
+        for (name, content) in documents_content {
+            index.insert(name.to_owned(), index.analyzer().tokenize_str(content));
+        }
+        let ranks = |query| index.query(query).into_iter()
+            .map(|r| (r.document, r.rank))
+            .collect::<Vec<_>>();
+        let rank = |document: &str, rank: f64| (document.to_owned(), rank);
+
+        assert_eq!(ranks("films"), vec![rank("a", 1.0), rank("b", 0.5)]);
+        assert_eq!(ranks("movie"), vec![rank("b", 1.0), rank("a", 0.5)]);
+        assert_eq!(ranks("flick"), vec![rank("a", 0.5)]);
+        assert_eq!(ranks("motion picture"), vec![rank("c", 2.0), rank("b", 1.0), rank("d", 1.0)]);
+        assert_eq!(ranks("good movie"), vec![rank("b", 2.0), rank("a", 1.5)]);
+        assert_eq!(ranks("picture"), vec![rank("c", 1.0), rank("d", 1.0)]);
+    }
+
+    #[test]
+    fn test_stats_and_term_info() {
+        let schema: Schema = serde_yaml::from_str("fields: [{name: title}, {name: text}]").unwrap();
+        let index = InvertedIndex::with_schema(Analyzer::default(), schema);
+        let documents_content = vec![
+            ("a", "Movies", "a movie about movies"),
+            ("b", "Songs", "songs from a movie"),
+            ("c", "Jazz", "songs and jazz"),
+        ];
+        for (name, title, text) in documents_content {
+            let fields = BTreeMap::from([("title".to_owned(), title.to_owned()), ("text".to_owned(), text.to_owned())]);
+            index.insert_fields(name.to_owned(), fields, DocumentInfo::default());
+        }
+
+        let stats = index.stats(2);
+        assert_eq!((stats.documents, stats.terms, stats.postings), (3, 10, 13));
+        assert!(stats.memory_estimate > 0);
+        let term_stats = |field: Option<&str>, term: &str, surface: &str, postings| TermStats {
+            field: field.map(str::to_owned),
+            term: term.to_owned(),
+            surface: surface.to_owned(),
+            postings,
+        };
+        assert_eq!(stats.most_frequent_terms, vec![
+            term_stats(None, "movi", "movie", 3),
+            term_stats(None, "song", "songs", 3),
+        ]);
+        assert_eq!(stats.longest_postings, vec![
+            term_stats(Some("text"), "a", "a", 2),
+            term_stats(Some("text"), "movi", "movie", 2),
+        ]);
+
+        let infos = index.term_info("Songs");
+        assert_eq!(infos.iter().map(|i| (i.field.as_str(), i.document_frequency)).collect::<Vec<_>>(),
+            vec![("title", 1), ("text", 2)]);
+        assert_eq!(infos[1].documents, vec!["b", "c"]);
+        assert!(index.term_info("unknown").is_empty());
+    }
+
+    #[test]
+    fn test_query_cache_invalidation() {
+        let index = index_with(vec![("a.txt", "great movie", 0)]);
+        assert_eq!(documents(index.query("movie")), vec!["a.txt"]);
+        assert_eq!(documents(index.query("  movie")), vec!["a.txt"]);
+        assert_eq!((index.stats(0).query_cache.hits, index.stats(0).query_cache.misses), (1, 1));
+
+        index.insert("b.txt".to_owned(), scan_for_unique_words("another movie").unwrap());
+        assert_eq!(documents(index.query("movie")), vec!["a.txt", "b.txt"]);
+        assert_eq!(index.stats(0).query_cache.misses, 2);
+        let options = QueryOptions::default();
+        assert!(Arc::ptr_eq(&index.search("movie", &options), &index.search("movie", &options)));
+
+        let uncached = index_with(vec![]).with_query_cache(QueryCacheConfig { capacity: 0, ..Default::default() });
+        uncached.query("movie");
+        assert_eq!(uncached.stats(0).query_cache, QueryCacheStats::default());
+    }
+
+    #[test]
+    fn test_per_document_language() {
+        let analyzer: Analyzer = serde_yaml::from_str("
+filters:
+  - type: lowercase
+  - type: stemmer
+    language: auto
+").unwrap();
+        let index = InvertedIndex::with_analyzer(analyzer);
+        let documents_content = vec![
+            ("en.txt", "The actors are playing wonderfully in this movie, but the story is far too long and the dialogues are boring"),
+            ("fr.txt", "Les acteurs jouent magnifiquement dans ce film, mais l'histoire est trop longue et les dialogues sont ennuyeux"),
+        ];
+        for (name, content) in documents_content {
+            let words = index.analyzer().tokenize_str(content);
+            index.insert(name.to_owned(), words);
+        }
+
+        assert_eq!(index.document_info(&"en.txt".to_owned()).language, Some(StemmerLanguage::English));
+        assert_eq!(index.document_info(&"fr.txt".to_owned()).language, Some(StemmerLanguage::French));
+        assert_eq!(documents(index.query("acteur")), vec!["fr.txt"]);
+        assert_eq!(documents(index.query("play")), vec!["en.txt"]);
+        assert_eq!(documents(index.query("dialogue")), vec!["en.txt", "fr.txt"]);
+
+        let french = QueryOptions { language: Some(StemmerLanguage::French), ..Default::default() };
+        assert_eq!(documents(index.query_with_options("playing", &french)), Vec::<String>::new());
+    }
+}
@@ -1,5 +1,17 @@
 pub mod word_filtering;
+pub mod analysis;
+pub mod stopwords;
+pub mod stemming;
+pub mod synonyms;
+pub mod schema;
 pub mod inverted_index;
+pub mod extraction;
+pub mod decoding;
+pub mod archives;
+pub mod records;
 pub mod fs_helpers;
 pub mod server;
-pub mod messages;
\ No newline at end of file
+pub mod messages;
+pub mod query;
+pub mod query_cache;
+pub mod frozen_index;
@@ -1,9 +1,9 @@
 use std::{sync::Arc, time::Instant, num::NonZeroUsize};
 
-use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 use log::{info, error, debug};
 
-use parallel_computing::{inverted_index::InvertedIndex, fs_helpers, server::Server};
+use parallel_computing::{inverted_index::{InvertedIndex, QueryOptions}, fs_helpers::{self, IngestOptions}, server::Server, analysis::Analyzer, decoding::InvalidUtf8, records::RecordOptions, schema::Schema, query_cache::QueryCacheConfig};
 use serde::Serialize;
 
 #[derive(Parser, Debug)]
@@ -29,6 +29,32 @@ enum Commands {
 
         #[arg(short = 'i', default_value = "10")]
         iterations: NonZeroUsize,
+
+        #[command(flatten)]
+        ingest: IngestArgs,
+    },
+    /// Times queries against the index while it takes documents and after it is frozen for serving
+    TimeQueries {
+        #[arg(short = 'd', long = "directory", action = ArgAction::Append)]
+        directories: Vec<String>,
+
+        /// Query to run, can be repeated
+        #[arg(short = 'q', long = "query", action = ArgAction::Append, required = true)]
+        queries: Vec<String>,
+
+        /// Number of threads inserting files
+        #[arg(short = 't', long = "thread-count", default_value = "1")]
+        thread_count: NonZeroUsize,
+
+        #[arg(short = 'o', default_value = "json")]
+        output_format: OutputFormat,
+
+        /// Number of times every query is run
+        #[arg(short = 'i', default_value = "100")]
+        iterations: NonZeroUsize,
+
+        #[command(flatten)]
+        ingest: IngestArgs,
     },
     Serve {
         #[arg(short = 's', long = "server-address", default_value = "127.0.0.1:8080")]
@@ -39,6 +65,82 @@ enum Commands {
 
         #[arg(short = 't', long = "thread-count", default_value = "1")]
         thread_count: NonZeroUsize,
+
+        #[command(flatten)]
+        ingest: IngestArgs,
+
+        /// Number of recent queries whose results are cached in front of the frozen index, 0 disables the cache
+        #[arg(long = "query-cache-size", default_value = "1024")]
+        query_cache_size: usize,
+
+        /// Queries with more results than this are not cached
+        #[arg(long = "query-cache-max-results", default_value = "10000")]
+        query_cache_max_results: usize,
+
+        /// Approximate number of bytes the cached results may take
+        #[arg(long = "query-cache-max-bytes", default_value = "67108864")]
+        query_cache_max_bytes: usize,
+    }
+}
+
+// How the files of `--directory` are read and indexed, shared by every subcommand that builds an index
+#[derive(Args, Debug)]
+struct IngestArgs {
+    /// YAML or JSON file describing the analyzer
+    #[arg(short = 'a', long = "analyzer")]
+    analyzer: Option<String>,
+
+    /// YAML or JSON file describing the fields of documents
+    #[arg(long = "schema")]
+    schema: Option<String>,
+
+    /// What to do with files that are not valid UTF-8
+    #[arg(long = "invalid-utf8", default_value = "windows1252")]
+    invalid_utf8: InvalidUtf8Cli,
+
+    /// Field of JSON Lines and CSV records holding the document ID
+    #[arg(long = "id-field")]
+    id_field: Option<String>,
+
+    /// Field of JSON Lines and CSV records holding the text to index, can be repeated
+    #[arg(long = "text-field", action = ArgAction::Append, default_value = "text")]
+    text_fields: Vec<String>,
+}
+
+impl IngestArgs {
+    fn analyzer(&self) -> Analyzer {
+        match &self.analyzer {
+            Some(path) => match Analyzer::from_file(path) {
+                Ok(analyzer) => analyzer,
+                Err(err) => {
+                    eprintln!("error reading analyzer from {}: {}", path, err);
+                    std::process::exit(1)
+                },
+            },
+            None => Analyzer::default(),
+        }
+    }
+
+    fn schema(&self) -> Schema {
+        match &self.schema {
+            Some(path) => match Schema::from_file(path) {
+                Ok(schema) => schema,
+                Err(err) => {
+                    eprintln!("error reading schema from {}: {}", path, err);
+                    std::process::exit(1)
+                },
+            },
+            None => Schema::default(),
+        }
+    }
+}
+
+impl From<&IngestArgs> for IngestOptions {
+    fn from(args: &IngestArgs) -> Self {
+        Self {
+            invalid_utf8: args.invalid_utf8.into(),
+            records: RecordOptions { id_field: args.id_field.clone(), text_fields: args.text_fields.clone() },
+        }
     }
 }
 
@@ -48,6 +150,23 @@ enum OutputFormat {
     Yaml,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum InvalidUtf8Cli {
+    Fail,
+    Windows1252,
+    Lossy,
+}
+
+impl From<InvalidUtf8Cli> for InvalidUtf8 {
+    fn from(invalid_utf8: InvalidUtf8Cli) -> Self {
+        match invalid_utf8 {
+            InvalidUtf8Cli::Fail => InvalidUtf8::Fail,
+            InvalidUtf8Cli::Windows1252 => InvalidUtf8::Windows1252,
+            InvalidUtf8Cli::Lossy => InvalidUtf8::Lossy,
+        }
+    }
+}
+
 fn main() {
     env_logger::init();
 
@@ -61,7 +180,11 @@ fn main() {
             thread_count_end,
             output_format,
             iterations,
+            ingest,
         } =>  {
+            let analyzer = ingest.analyzer();
+            let schema = ingest.schema();
+            let ingest_options = IngestOptions::from(&ingest);
             if thread_count_end < thread_count_start {
                 eprintln!("thread-start should be less than or equal to thread-end");
                 std::process::exit(1)
@@ -79,11 +202,11 @@ fn main() {
                 let index_construction_start = Instant::now();
 
                 for _ in 0..iterations {
-                    let inverted_index = Arc::new(InvertedIndex::new());
-                    fs_helpers::insert_files_into_inverted_index(Arc::clone(&files), &inverted_index, thread_count);
+                    let inverted_index = Arc::new(InvertedIndex::with_schema(analyzer.clone(), schema.clone()));
+                    fs_helpers::insert_files_into_inverted_index(Arc::clone(&files), &inverted_index, thread_count, &ingest_options);
                 }
 
-                return index_construction_start.elapsed().as_nanos() / iterations as u128;
+                index_construction_start.elapsed().as_nanos() / iterations as u128
             };
 
             #[derive(Serialize)]
@@ -104,22 +227,89 @@ fn main() {
             };
             println!("{}", results);
         },
+        Commands::TimeQueries {
+            directories,
+            queries,
+            thread_count,
+            output_format,
+            iterations,
+            ingest,
+        } => {
+            let ingest_options = IngestOptions::from(&ingest);
+            let iterations = usize::from(iterations);
+
+            let files = fs_helpers::get_file_paths_from_directories(directories.iter());
+            eprintln!("{} files found", files.len());
+            // every run has to do the work, so results are never cached
+            let query_cache = QueryCacheConfig { capacity: 0, ..Default::default() };
+            let inverted_index = Arc::new(InvertedIndex::with_schema(ingest.analyzer(), ingest.schema())
+                .with_query_cache(query_cache));
+            let report = fs_helpers::insert_files_into_inverted_index(Arc::new(files), &inverted_index, usize::from(thread_count), &ingest_options);
+            eprintln!("{}", report);
+
+            let options = QueryOptions::default();
+            let time = |search: &dyn Fn(&str)| {
+                let start = Instant::now();
+                for _ in 0..iterations {
+                    for query in &queries {
+                        search(query);
+                    }
+                }
+                start.elapsed().as_nanos() / (iterations * queries.len()) as u128
+            };
+
+            #[derive(Serialize)]
+            struct ResultInstance {
+                index: &'static str,
+                time: u128,
+            }
+
+            let building = time(&|query| { inverted_index.search(query, &options); });
+            let frozen_index = Arc::into_inner(inverted_index)
+                .expect("ingestion threads are done with the index")
+                .freeze();
+            let frozen = time(&|query| { frozen_index.search(query, &options); });
+            let results = vec![
+                ResultInstance { index: "building", time: building },
+                ResultInstance { index: "frozen", time: frozen },
+            ];
+
+            let results = match output_format {
+                OutputFormat::Json => serde_json::to_string(&results).unwrap(),
+                OutputFormat::Yaml => serde_yaml::to_string(&results).unwrap(),
+            };
+            println!("{}", results);
+        },
         Commands::Serve {
             server_address,
             directories,
             thread_count,
+            ingest,
+            query_cache_size,
+            query_cache_max_results,
+            query_cache_max_bytes,
         } => {
             let thread_count = usize::from(thread_count);
 
-            let inverted_index = Arc::new(InvertedIndex::new());
+            let inverted_index = Arc::new(InvertedIndex::with_schema(ingest.analyzer(), ingest.schema()));
             if let Some(directories) = directories {
                 info!("Constructing index from files in provided directories");
                 let files = fs_helpers::get_file_paths_from_directories(directories.iter());
-                fs_helpers::insert_files_into_inverted_index(Arc::new(files), &inverted_index, thread_count);
+                let ingest_options = IngestOptions::from(&ingest);
+                let report = fs_helpers::insert_files_into_inverted_index(Arc::new(files), &inverted_index, thread_count, &ingest_options);
+                info!("{}", report);
             }
+            let inverted_index = Arc::into_inner(inverted_index)
+                .expect("ingestion threads are done with the index")
+                .freeze();
         
             info!("serving at {}...", server_address);
-            let mut server = Server::new(inverted_index, thread_count);
+            let query_cache = QueryCacheConfig {
+                capacity: query_cache_size,
+                max_results: query_cache_max_results,
+                max_bytes: query_cache_max_bytes,
+            };
+            let mut server = Server::new(Arc::new(inverted_index), thread_count).with_query_cache(query_cache);
             if let Err(err) = server.listen(server_address) {
                 error!("critical server error: {}", err);
             }
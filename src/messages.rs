@@ -1,9 +1,10 @@
-use std::{io::{Read, self, Write, Error, ErrorKind}, fs::File, path::Path};
+use std::{io::{Read, self, Write, Error, ErrorKind}, fs::File, path::Path, sync::Arc};
 
 use byteorder::{WriteBytesExt, BigEndian, ReadBytesExt};
+use serde::{Serialize, Deserialize};
 use serde_json::json;
 
-use crate::inverted_index::QueryResult;
+use crate::{inverted_index::{QueryOptions, SearchResults, IndexStats, TermInfo}, archives, records::{RecordFormat, RECORD_SEPARATOR}};
 
 pub struct Message {
     kind: u8,
@@ -29,6 +30,13 @@ impl StreamContent {
             stream: Box::new(f)
         })
     }
+
+    fn from_bytes(bytes: Vec<u8>) -> Self {
+        StreamContent {
+            len: bytes.len() as u64,
+            stream: Box::new(io::Cursor::new(bytes)),
+        }
+    }
 }
 
 impl std::fmt::Debug for MessageContent {
@@ -74,7 +82,7 @@ impl Message {
                 MessageContent::String(s) => {
                     let mut s_bytes = s.as_bytes();
                     while !s_bytes.is_empty() {
-                        let written = stream.write(&s_bytes)?;
+                        let written = stream.write(s_bytes)?;
                         s_bytes = &s_bytes[written..];
                     }
                 },
@@ -91,7 +99,7 @@ impl Message {
         let kind = stream.read_u8()?;
         let len = stream.read_u64::<BigEndian>()?;
         let content = if len > 0 {
-            let mut buf = vec![0 as u8; len as usize];
+            let mut buf = vec![0_u8; len as usize];
             stream.read_exact(&mut buf[..])?;
             let s = String::from_utf8(buf).or(Err(Error::new(
                 ErrorKind::InvalidData, "payload is not a valid UTF8 string")))?;
@@ -103,7 +111,7 @@ impl Message {
         Ok(Self { kind, len, content })
     }
 
-    pub fn from_string<'a>(kind: u8, s: String) -> Self {
+    pub fn from_string(kind: u8, s: String) -> Self {
         Message { 
             kind, 
             len: s.len() as u64, 
@@ -128,7 +136,67 @@ impl Message {
 pub enum Request {
     Ping,
     Query(String),
-    QueryFile(String)
+    QueryFile(String),
+    QueryWithOptions(QueryRequest),
+    MoreLikeThis(MoreLikeThisRequest),
+    Stats(StatsRequest),
+    /// Looks up the terms a word is analyzed into
+    Term(String),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryRequest {
+    pub query: String,
+    #[serde(default)]
+    pub options: QueryOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoreLikeThisRequest {
+    /// Path of a document in the index, like the ones in query results
+    pub document: String,
+    /// Number of the document's most distinctive terms to look up
+    #[serde(default = "default_max_terms")]
+    pub max_terms: usize,
+    /// Number of the most similar documents to return
+    #[serde(default = "default_max_results")]
+    pub max_results: usize,
+}
+
+impl MoreLikeThisRequest {
+    /// A request for `document` with the default limits
+    pub fn new(document: String) -> Self {
+        Self {
+            document,
+            max_terms: default_max_terms(),
+            max_results: default_max_results(),
+        }
+    }
+}
+
+fn default_max_terms() -> usize {
+    25
+}
+
+fn default_max_results() -> usize {
+    10
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatsRequest {
+    /// Number of most frequent terms and longest posting lists to list
+    #[serde(default = "default_top")]
+    pub top: usize,
+}
+
+impl Default for StatsRequest {
+    fn default() -> Self {
+        Self { top: default_top() }
+    }
+}
+
+fn default_top() -> usize {
+    10
 }
 
 impl FromMessage for Request {
@@ -139,6 +207,19 @@ impl FromMessage for Request {
             0 => Self::Ping,
             1 => Self::Query(requires_payload(content, "Query")?),
             2 => Self::QueryFile(requires_payload(content, "QueryFile")?),
+            3 => {
+                let content = requires_payload(content, "QueryWithOptions")?;
+                Self::QueryWithOptions(serde_json::from_str(&content)?)
+            },
+            4 => {
+                let content = requires_payload(content, "MoreLikeThis")?;
+                Self::MoreLikeThis(serde_json::from_str(&content)?)
+            },
+            5 => {
+                let content = requires_payload(content, "Stats")?;
+                Self::Stats(serde_json::from_str(&content)?)
+            },
+            6 => Self::Term(requires_payload(content, "Term")?),
             x => return Err(Error::new(ErrorKind::InvalidInput, 
                 format!("request kind {} does not exist", x)))
         };
@@ -152,6 +233,13 @@ impl IntoMessage for Request {
             Request::Ping => Message::empty(0),
             Request::Query(s) => Message::from_string(1, s),
             Request::QueryFile(s) => Message::from_string(2, s),
+            Request::QueryWithOptions(r) =>
+                Message::from_string(3, json!(r).to_string()),
+            Request::MoreLikeThis(r) =>
+                Message::from_string(4, json!(r).to_string()),
+            Request::Stats(r) =>
+                Message::from_string(5, json!(r).to_string()),
+            Request::Term(s) => Message::from_string(6, s),
         }
     }
 }
@@ -160,12 +248,37 @@ impl IntoMessage for Request {
 pub enum Response {
     Pong,
     Error(String),
-    QueryResult(Vec<QueryResult>),
-    FileResult(MessageContent)
+    QueryResult(QueryResponse),
+    FileResult(MessageContent),
+    Stats(IndexStats),
+    TermInfo(Vec<TermInfo>),
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QueryResponse {
+    /// Shared with the server's query cache, so cached results are written without being copied
+    #[serde(flatten)]
+    pub search: Arc<SearchResults>,
+    /// Corrected queries, only filled in when there are no results
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
 }
 
 impl Response {
     pub fn from_file_path(s: &String) -> io::Result<Self> {
+        let is_record = s.rsplit_once(RECORD_SEPARATOR)
+            .is_some_and(|(file, _)| RecordFormat::from_path(Path::new(file)).is_some());
+        if is_record && !Path::new(s).exists() {
+            return Ok(Self::Error("path is a record, which can't be read as a file".to_owned()));
+        }
+        if archives::is_archived(s) {
+            return match archives::read_document(s) {
+                Ok(content) => Ok(Self::FileResult(MessageContent::Stream(StreamContent::from_bytes(content)))),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::Error("file does not exist".to_owned())),
+                Err(err) => Err(err),
+            };
+        }
+
         let path = Path::new(s);
         if !path.exists() {
             return Ok(Self::Error("file does not exist".to_owned()));
@@ -184,13 +297,17 @@ impl IntoMessage for Response {
         match self {
             Self::Pong => Message::empty(0),
             Self::Error(s) => Message::from_string(1, s),
-            Self::QueryResult(v) => 
-                Message::from_string(2, json!(v).to_string()),
+            Self::QueryResult(v) => Message::from_string(2,
+                serde_json::to_string(&v).expect("query results serialize to JSON")),
             Self::FileResult(content) => match content {
                 MessageContent::String(s) => Message::from_string(3, s),
                 MessageContent::Stream(stream_content) =>
                     Message::from_stream_content(3, stream_content),
             },
+            Self::Stats(stats) =>
+                Message::from_string(4, json!(stats).to_string()),
+            Self::TermInfo(infos) =>
+                Message::from_string(5, json!(infos).to_string()),
         }
     }
 }
@@ -204,11 +321,19 @@ impl FromMessage for Response {
             1 => Self::Error(requires_payload(content, "Error")?),
             2 => {
                 let content = requires_payload(content, "QueryResult")?;
-                let v = serde_json::from_str::<Vec<QueryResult>>(&content)?;
+                let v = serde_json::from_str::<QueryResponse>(&content)?;
                 Self::QueryResult(v)
             },
             3 => Self::FileResult(MessageContent::String(
                 requires_payload(content, "FileResult")?)),
+            4 => {
+                let content = requires_payload(content, "Stats")?;
+                Self::Stats(serde_json::from_str(&content)?)
+            },
+            5 => {
+                let content = requires_payload(content, "TermInfo")?;
+                Self::TermInfo(serde_json::from_str(&content)?)
+            },
             x => return Err(Error::new(ErrorKind::InvalidInput, 
                 format!("response kind {} does not exist", x)))
         };
@@ -223,6 +348,6 @@ fn requires_payload(content: Option<MessageContent>, message_kind: &str) -> io::
         Ok(s)
     } else {
         Err(Error::new(ErrorKind::Unsupported, 
-            format!("messages with a stream payload are not supported for reading")))
+            "messages with a stream payload are not supported for reading"))
     }
 }
@@ -0,0 +1,475 @@
+use std::{cmp::Ordering, collections::BTreeSet, fmt, path::Path, time::{Duration, SystemTime}};
+
+use serde_json::Value;
+
+use crate::{analysis::Analyzer, schema::Schema, inverted_index::DocumentInfo};
+
+/// Attributes every document ingested from files has, besides the metadata of records
+pub const BUILTIN_ATTRIBUTES: [&str; 4] = ["ext", "dir", "mtime", "size"];
+
+/// A single unit of a query that contributes to a document's rank on its own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryClause {
+    /// A plain word, looked up by its stem
+    Word(String),
+    /// A pattern where `*` matches any run of characters and `?` matches exactly one
+    Wildcard(String),
+    /// An inclusive range of terms, written as `from..to`
+    Range { from: String, to: String },
+    /// A word matched against terms within an edit distance, written as `word~` or `word~2`.
+    /// Without an explicit distance the one from the query options is used
+    Fuzzy { word: String, max_distance: Option<usize> },
+    /// A clause matched only against one field, written as `field:clause`
+    Field { field: String, clause: Box<QueryClause> },
+    /// Consecutive query words along with the phrases they are synonyms of.
+    /// Made by expanding `Word`s with the analyzer's synonyms, never by `parse_query`
+    Synonyms { words: Vec<String>, synonyms: Vec<Vec<String>> },
+    /// A condition on an attribute of documents that doesn't contribute to the rank, like `ext:txt` or `size<10k`
+    Filter(Filter),
+}
+
+/// Compares an attribute of a document with `value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    /// One of `BUILTIN_ATTRIBUTES` or a metadata key
+    pub attribute: String,
+    pub comparison: Comparison,
+    pub value: String,
+}
+
+/// Written as `:`, `<`, `<=`, `>` and `>=`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+/// Splits `query` on whitespace and classifies each chunk.
+///
+/// Chunks where a literal prefix is followed by `*` or `?` become `Wildcard`, a trailing `?` is punctuation
+/// and not part of the pattern. Chunks of the form `from..to` become `Range`,
+/// chunks ending with `~` or `~N` become `Fuzzy`. Structured tokens recognized by the tokenizer, like URLs,
+/// are never treated as wildcards. A chunk prefixed with the name of an indexed field of `schema` and `:`
+/// is classified the same way, but only matches that field. Otherwise a chunk comparing one of `BUILTIN_ATTRIBUTES`
+/// or `metadata_keys` with a value, like `ext:txt` or `rating>=7`, becomes `Filter`.
+/// Everything else is tokenized with `analyzer` the same way documents are.
+pub fn parse_query(query: &str, analyzer: &Analyzer, schema: &Schema, metadata_keys: &BTreeSet<String>) -> Vec<QueryClause> {
+    let mut clauses = vec![];
+    for chunk in query.split_whitespace() {
+        let field = chunk.split_once(':')
+            .filter(|(field, rest)| !rest.is_empty() && schema.field(field).is_some_and(|f| f.indexed));
+        if let Some((field, rest)) = field {
+            clauses.extend(parse_chunk(rest, analyzer)
+                .into_iter()
+                .map(|clause| QueryClause::Field { field: field.to_owned(), clause: Box::new(clause) }));
+        } else if let Some(filter) = parse_filter(chunk, metadata_keys) {
+            clauses.push(QueryClause::Filter(filter));
+        } else {
+            clauses.extend(parse_chunk(chunk, analyzer));
+        }
+    }
+    clauses
+}
+
+fn parse_filter(chunk: &str, metadata_keys: &BTreeSet<String>) -> Option<Filter> {
+    let i = chunk.find([':', '<', '>'])?;
+    let (attribute, rest) = chunk.split_at(i);
+    if !BUILTIN_ATTRIBUTES.contains(&attribute) && !metadata_keys.contains(attribute) {
+        return None;
+    }
+    let (comparison, value) = [
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        (":", Comparison::Equal),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ].into_iter().find_map(|(operator, comparison)| rest.strip_prefix(operator).map(|value| (comparison, value)))?;
+    if value.is_empty() {
+        return None;
+    }
+    Some(Filter { attribute: attribute.to_owned(), comparison, value: value.to_owned() })
+}
+
+/// Writes a clause back in query syntax. Synonyms are written as the query words followed by the synonyms in parentheses
+impl fmt::Display for QueryClause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word(word) | Self::Wildcard(word) => write!(f, "{}", word),
+            Self::Range { from, to } => write!(f, "{}..{}", from, to),
+            Self::Fuzzy { word, max_distance: Some(distance) } => write!(f, "{}~{}", word, distance),
+            Self::Fuzzy { word, max_distance: None } => write!(f, "{}~", word),
+            Self::Field { field, clause } => write!(f, "{}:{}", field, clause),
+            Self::Synonyms { words, synonyms } => {
+                let synonyms: Vec<String> = synonyms.iter().map(|synonym| synonym.join(" ")).collect();
+                write!(f, "{} ({})", words.join(" "), synonyms.join(", "))
+            },
+            Self::Filter(Filter { attribute, comparison, value }) => {
+                let operator = match comparison {
+                    Comparison::Equal => ":",
+                    Comparison::Less => "<",
+                    Comparison::LessOrEqual => "<=",
+                    Comparison::Greater => ">",
+                    Comparison::GreaterOrEqual => ">=",
+                };
+                write!(f, "{}{}{}", attribute, operator, value)
+            },
+        }
+    }
+}
+
+impl Comparison {
+    /// Whether an attribute ordered `ordering` relative to the filter value passes
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Equal => ordering.is_eq(),
+            Self::Less => ordering.is_lt(),
+            Self::LessOrEqual => ordering.is_le(),
+            Self::Greater => ordering.is_gt(),
+            Self::GreaterOrEqual => ordering.is_ge(),
+        }
+    }
+}
+
+impl Filter {
+    /// Whether a document passes the filter. Documents without the attribute,
+    /// and every document if the value can't be read as the attribute's type, never pass.
+    ///
+    /// `ext` is compared without a leading dot and ignoring case, `dir:path` matches directories ending with `path`,
+    /// `size` accepts `k`, `m` and `g` suffixes, and `mtime` accepts dates like `2022-01-01`, which span the whole day.
+    /// Metadata is compared as numbers when both sides are numbers and as text ignoring case otherwise.
+    /// A metadata list passes if any of its values does
+    pub fn matches(&self, info: &DocumentInfo) -> bool {
+        match self.attribute.as_str() {
+            "ext" => info.extension.as_ref().is_some_and(|extension| {
+                let value = self.value.trim_start_matches('.').to_lowercase();
+                self.comparison.holds(extension.to_lowercase().cmp(&value))
+            }),
+            "dir" => info.directory.as_ref().is_some_and(|directory| match self.comparison {
+                Comparison::Equal => Path::new(directory).ends_with(&self.value),
+                comparison => comparison.holds(directory.as_str().cmp(&self.value)),
+            }),
+            "size" => match (info.size, parse_size(&self.value)) {
+                (Some(size), Some(value)) => self.comparison.holds(size.cmp(&value)),
+                _ => false,
+            },
+            "mtime" => match (info.modified, parse_time(&self.value)) {
+                (Some(modified), Some((start, span))) => {
+                    let ordering = if modified < start {
+                        Ordering::Less
+                    } else if modified >= start + span {
+                        Ordering::Greater
+                    } else {
+                        Ordering::Equal
+                    };
+                    self.comparison.holds(ordering)
+                },
+                _ => false,
+            },
+            key => info.metadata.get(key).is_some_and(|value| self.matches_value(value)),
+        }
+    }
+
+    fn matches_value(&self, value: &Value) -> bool {
+        let text = match value {
+            Value::Array(values) => return values.iter().any(|value| self.matches_value(value)),
+            Value::Null | Value::Object(_) => return false,
+            Value::String(text) => text.clone(),
+            value => value.to_string(),
+        };
+        match (text.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(number), Ok(value)) => self.comparison.holds(number.total_cmp(&value)),
+            _ => self.comparison.holds(text.to_lowercase().cmp(&self.value.to_lowercase())),
+        }
+    }
+}
+
+/// Parses a number of bytes with an optional `k`, `m` or `g` suffix for powers of 1024, like `10k` or `1.5MB`
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.to_ascii_lowercase();
+    let s = s.strip_suffix('b').unwrap_or(&s);
+    let (number, multiplier) = match s.chars().last()? {
+        'k' => (&s[..s.len() - 1], 1 << 10),
+        'm' => (&s[..s.len() - 1], 1 << 20),
+        'g' => (&s[..s.len() - 1], 1 << 30),
+        _ => (s, 1),
+    };
+    let number: f64 = number.parse().ok().filter(|n: &f64| *n >= 0.0)?;
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Parses a UTC date like `2022-01-01` or a time like `2022-01-01T12:30:00`
+/// into its start and the span of time it covers, a day or a second
+pub fn parse_time(s: &str) -> Option<(SystemTime, Duration)> {
+    match humantime::parse_rfc3339_weak(s) {
+        Ok(time) => Some((time, Duration::from_secs(1))),
+        Err(_) => humantime::parse_rfc3339_weak(&format!("{}T00:00:00", s))
+            .ok()
+            .map(|time| (time, Duration::from_secs(24 * 60 * 60))),
+    }
+}
+
+fn parse_chunk(chunk: &str, analyzer: &Analyzer) -> Vec<QueryClause> {
+    if let Some((word, distance)) = chunk.rsplit_once('~') {
+        let distance_is_valid = distance.is_empty() || distance.parse::<usize>().is_ok();
+        if !word.is_empty() && distance_is_valid {
+            return vec![QueryClause::Fuzzy {
+                word: word.to_owned(),
+                max_distance: distance.parse().ok(),
+            }];
+        }
+    }
+    // a `?` or other punctuation closing the chunk, like in `is it good?`, is not part of the pattern
+    let pattern = chunk.trim_end_matches(|c: char| c != '*' && !c.is_alphanumeric());
+    let is_wildcard = pattern.contains(['*', '?']) && !literal_prefix(pattern).is_empty();
+    if is_wildcard && !analyzer.tokenizer.recognizes(chunk) {
+        return vec![QueryClause::Wildcard(pattern.to_owned())];
+    }
+    if let Some((from, to)) = chunk.split_once("..") {
+        if !from.is_empty() && !to.is_empty() {
+            return vec![QueryClause::Range {
+                from: from.to_owned(),
+                to: to.to_owned(),
+            }];
+        }
+    }
+    let mut words: Vec<String> = analyzer.tokenize_str(chunk).into_iter().collect();
+    words.sort();
+    words.into_iter().map(QueryClause::Word).collect()
+}
+
+/// Returns the part of a wildcard `pattern` before its first wildcard character
+pub fn literal_prefix(pattern: &str) -> &str {
+    match pattern.find(['*', '?']) {
+        Some(i) => &pattern[..i],
+        None => pattern,
+    }
+}
+
+/// Matches `s` against a `pattern` where `*` matches any run of characters
+/// (including an empty one) and `?` matches exactly one character
+pub fn wildcard_matches(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+
+    let (mut p, mut i) = (0, 0);
+    // position of the last `*` in pattern and the position in `s` it was matched at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while i < s.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, i));
+                p += 1;
+            },
+            Some(&c) if c == '?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            },
+            _ => match backtrack {
+                Some((star_p, star_i)) => {
+                    p = star_p + 1;
+                    i = star_i + 1;
+                    backtrack = Some((star_p, star_i + 1));
+                },
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Levenshtein distance between `a` and `b` counted in characters.
+///
+/// Returns `None` as soon as the distance is known to exceed `max_distance`
+pub fn bounded_edit_distance(a: &str, b: &str, max_distance: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max_distance {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let substitution = previous[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            current[j] = substitution
+                .min(previous[j] + 1)
+                .min(current[j - 1] + 1);
+        }
+        if current.iter().min().unwrap() > &max_distance {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    Some(previous[b.len()]).filter(|&d| d <= max_distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_query() {
+        use QueryClause::*;
+
+        let test_cases = vec![
+            ("stream*", vec![Wildcard("stream*".to_owned())]),
+            ("Col?r films", vec![Wildcard("Col?r".to_owned()), Word("films".to_owned())]),
+            ("is it good?", vec![Word("is".to_owned()), Word("it".to_owned()), Word("good".to_owned())]),
+            ("what?! col?r? stream*.", vec![
+                Word("what".to_owned()),
+                Wildcard("col?r".to_owned()),
+                Wildcard("stream*".to_owned()),
+            ]),
+            ("*ing ?", vec![Word("ing".to_owned())]),
+            ("apple..banana", vec![Range { from: "apple".to_owned(), to: "banana".to_owned() }]),
+            ("well.. it's bad", vec![Word("well".to_owned()), Word("it's".to_owned()), Word("bad".to_owned())]),
+            ("movei~ film~2", vec![
+                Fuzzy { word: "movei".to_owned(), max_distance: None },
+                Fuzzy { word: "film".to_owned(), max_distance: Some(2) },
+            ]),
+            ("~ a~b", vec![Word("a".to_owned()), Word("b".to_owned())]),
+            ("  ", vec![]),
+        ];
+
+        for (query, expected) in test_cases {
+            assert_eq!(parse_query(query, &Analyzer::default(), &Schema::default(), &BTreeSet::new()), expected, "query `{}`", query);
+        }
+
+        let analyzer: Analyzer = serde_yaml::from_str("tokenizer: {recognize: [url]}").unwrap();
+        assert_eq!(parse_query("https://imdb.com/find?q=simbu", &analyzer, &Schema::default(), &BTreeSet::new()),
+            vec![Word("https://imdb.com/find?q=simbu".to_owned())]);
+
+        let schema: Schema = serde_yaml::from_str("fields: [{name: text}, {name: title}, {name: author, indexed: false}]").unwrap();
+        let field = |clause| Field { field: "title".to_owned(), clause: Box::new(clause) };
+        assert_eq!(parse_query("title:Sinatra title:movei~ author:frank title: text", &Analyzer::default(), &schema, &BTreeSet::new()), vec![
+            field(Word("Sinatra".to_owned())),
+            field(Fuzzy { word: "movei".to_owned(), max_distance: None }),
+            Word("author".to_owned()),
+            Word("frank".to_owned()),
+            Word("title".to_owned()),
+            Word("text".to_owned()),
+        ]);
+
+        let filter = |attribute: &str, comparison, value: &str| Filter(super::Filter {
+            attribute: attribute.to_owned(),
+            comparison,
+            value: value.to_owned(),
+        });
+        let metadata_keys = BTreeSet::from(["rating".to_owned()]);
+        assert_eq!(parse_query("great ext:txt size<10k mtime>=2022-01-01 rating>7 dir: votes:3", &Analyzer::default(), &Schema::default(), &metadata_keys), vec![
+            Word("great".to_owned()),
+            filter("ext", Comparison::Equal, "txt"),
+            filter("size", Comparison::Less, "10k"),
+            filter("mtime", Comparison::GreaterOrEqual, "2022-01-01"),
+            filter("rating", Comparison::Greater, "7"),
+            Word("dir".to_owned()),
+            Word("3".to_owned()),
+            Word("votes".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_display_clauses() {
+        let schema: Schema = serde_yaml::from_str("fields: [{name: text}, {name: title}]").unwrap();
+        let query = "stream* apple..banana movei~ film~2 title:sinatra size>=10k";
+        let clauses = parse_query(query, &Analyzer::default(), &schema, &BTreeSet::new());
+        assert_eq!(clauses.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" "), query);
+
+        let synonyms = QueryClause::Synonyms {
+            words: vec!["film".to_owned()],
+            synonyms: vec![vec!["movie".to_owned()], vec!["motion".to_owned(), "picture".to_owned()]],
+        };
+        assert_eq!(synonyms.to_string(), "film (movie, motion picture)");
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let info = DocumentInfo {
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_641_038_400)), // 2022-01-01T12:00:00Z
+            size: Some(2048),
+            extension: Some("txt".to_owned()),
+            directory: Some("../aclImdb/train/pos".to_owned()),
+            metadata: serde_json::from_str(r#"{"rating": 9, "votes": "120", "genres": ["Drama", "Action"]}"#).unwrap(),
+            ..Default::default()
+        };
+        let test_cases = vec![
+            ("ext:txt", true),
+            ("ext:.TXT", true),
+            ("ext:md", false),
+            ("dir:train/pos", true),
+            ("dir:pos", true),
+            ("dir:os", false),
+            ("size<10k", true),
+            ("size>2k", false),
+            ("size>=2kb", true),
+            ("size<ten", false),
+            ("mtime:2022-01-01", true),
+            ("mtime>2022-01-01", false),
+            ("mtime>=2022-01-01", true),
+            ("mtime<2022-01-01T12:00:01", true),
+            ("mtime>2021-12-31", true),
+            ("rating>=9", true),
+            ("rating<9", false),
+            ("votes>99", true),
+            ("genres:drama", true),
+            ("genres:comedy", false),
+            ("missing:value", false),
+        ];
+
+        let metadata_keys = info.metadata.keys().cloned().chain(["missing".to_owned()]).collect();
+        for (query, expected) in test_cases {
+            let clauses = parse_query(query, &Analyzer::default(), &Schema::default(), &metadata_keys);
+            let QueryClause::Filter(filter) = &clauses[0] else {
+                panic!("`{}` is not a filter", query);
+            };
+            assert_eq!(filter.matches(&info), expected, "filter `{}`", query);
+        }
+    }
+
+    #[test]
+    fn test_wildcard_matches() {
+        let test_cases = vec![
+            ("stream*", "stream", true),
+            ("stream*", "streamer", true),
+            ("stream*", "strea", false),
+            ("col?r", "color", true),
+            ("col?r", "colour", false),
+            ("*our", "colour", true),
+            ("c*l*r", "colour", true),
+            ("c*l*r", "colours", false),
+            ("c*l*r", "cellar", true),
+            ("ワク*", "ワクワク", true),
+            ("?", "", false),
+            ("*", "", true),
+        ];
+
+        for (pattern, s, expected) in test_cases {
+            assert_eq!(wildcard_matches(pattern, s), expected, "pattern `{}`, string `{}`", pattern, s);
+        }
+    }
+
+    #[test]
+    fn test_bounded_edit_distance() {
+        let test_cases = vec![
+            ("movei", "movi", 2, Some(1)),
+            ("movie", "movie", 0, Some(0)),
+            ("kitten", "sitting", 3, Some(3)),
+            ("kitten", "sitting", 2, None),
+            ("", "abc", 3, Some(3)),
+            ("ワクワク", "ワクワ", 1, Some(1)),
+            ("short", "much longer", 2, None),
+        ];
+
+        for (a, b, max_distance, expected) in test_cases {
+            assert_eq!(bounded_edit_distance(a, b, max_distance), expected,
+                "`{}` and `{}` within {}", a, b, max_distance);
+        }
+    }
+}
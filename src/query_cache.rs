@@ -0,0 +1,260 @@
+use std::{collections::hash_map::RandomState, hash::BuildHasher, num::NonZeroUsize, sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}}};
+
+use lru::LruCache;
+use serde::{Serialize, Deserialize};
+
+use crate::inverted_index::{QueryOptions, QueryResult, SearchResults};
+
+/// Limits of a `QueryCache`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueryCacheConfig {
+    /// Maximum number of cached queries, the least recently used one is evicted first. 0 disables the cache
+    pub capacity: usize,
+    /// Queries with more results than this are not cached
+    pub max_results: usize,
+    /// Approximate number of bytes the cached results may take, least recently used ones are evicted past it
+    pub max_bytes: usize,
+}
+
+impl Default for QueryCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            max_results: 10_000,
+            max_bytes: 64 << 20,
+        }
+    }
+}
+
+/// Caches of at most this many entries have a single shard
+const ENTRIES_PER_SHARD: usize = 64;
+const MAX_SHARDS: usize = 16;
+
+/// Results of recent queries, keyed by the query with its whitespace normalized along with its options.
+///
+/// Every entry remembers the generation of the index it was computed at,
+/// and entries from older generations are never returned.
+/// Keys are spread over shards locked separately, each evicting its own least recently used entries,
+/// and results are shared, so a lock is only held to look up an entry and never while results are copied
+#[derive(Debug)]
+pub struct QueryCache {
+    config: QueryCacheConfig,
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Shard {
+    entries: LruCache<String, Entry>,
+    max_bytes: usize,
+    bytes: usize,
+}
+
+#[derive(Debug)]
+struct Entry {
+    generation: u64,
+    results: Arc<SearchResults>,
+    bytes: usize,
+}
+
+/// How well a `QueryCache` is doing
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueryCacheStats {
+    pub capacity: usize,
+    pub entries: usize,
+    /// Approximate size of the cached results
+    pub bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl QueryCache {
+    pub fn new(config: QueryCacheConfig) -> Self {
+        let shard_count = match config.capacity {
+            0 => 0,
+            capacity => capacity.div_ceil(ENTRIES_PER_SHARD).min(MAX_SHARDS),
+        };
+        let shards = (0..shard_count)
+            .map(|_| Mutex::new(Shard {
+                entries: LruCache::new(NonZeroUsize::new(config.capacity.div_ceil(shard_count)).unwrap()),
+                max_bytes: config.max_bytes / shard_count,
+                bytes: 0,
+            }))
+            .collect();
+        Self {
+            config,
+            shards,
+            hasher: RandomState::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn key(query: &str, options: &QueryOptions) -> String {
+        let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+        format!("{}\n{}", query, serde_json::to_string(options).unwrap())
+    }
+
+    fn shard(&self, key: &str) -> Option<&Mutex<Shard>> {
+        if self.shards.is_empty() {
+            return None;
+        }
+        let hash = self.hasher.hash_one(key) as usize;
+        Some(&self.shards[hash % self.shards.len()])
+    }
+
+    /// Cached results of `key`, if they were computed at `generation`
+    pub fn get(&self, key: &str, generation: u64) -> Option<Arc<SearchResults>> {
+        let mut shard = self.shard(key)?.lock().unwrap();
+        let results = match shard.entries.get(key) {
+            Some(entry) if entry.generation == generation => Some(Arc::clone(&entry.results)),
+            Some(_) => {
+                shard.remove(key);
+                None
+            },
+            None => None,
+        };
+        drop(shard);
+        let counter = if results.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        results
+    }
+
+    /// Caches `results` unless they have more than `max_results` results or would take more than a shard's share of `max_bytes`
+    pub fn insert(&self, key: String, generation: u64, results: Arc<SearchResults>) {
+        let Some(shard) = self.shard(&key) else {
+            return;
+        };
+        if results.results.len() > self.config.max_results {
+            return;
+        }
+        let bytes = key.len() + approximate_size(&results);
+        let mut shard = shard.lock().unwrap();
+        if bytes > shard.max_bytes {
+            return;
+        }
+        shard.bytes += bytes;
+        if let Some((_, replaced)) = shard.entries.push(key, Entry { generation, results, bytes }) {
+            shard.bytes -= replaced.bytes;
+        }
+        while shard.bytes > shard.max_bytes {
+            let Some((_, evicted)) = shard.entries.pop_lru() else {
+                break;
+            };
+            shard.bytes -= evicted.bytes;
+        }
+    }
+
+    pub fn stats(&self) -> QueryCacheStats {
+        let mut stats = QueryCacheStats {
+            capacity: self.config.capacity,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            ..Default::default()
+        };
+        for shard in &self.shards {
+            let shard = shard.lock().unwrap();
+            stats.entries += shard.entries.len();
+            stats.bytes += shard.bytes;
+        }
+        stats
+    }
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self::new(QueryCacheConfig::default())
+    }
+}
+
+impl Shard {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.pop(key) {
+            self.bytes -= entry.bytes;
+        }
+    }
+}
+
+/// Bytes taken by `results` on the heap and inline, counting strings by their length
+fn approximate_size(results: &SearchResults) -> usize {
+    let result_size = |result: &QueryResult| {
+        size_of::<QueryResult>()
+            + result.document.len()
+            + result.fields.iter().map(|(name, text)| name.len() + text.len() + 2 * size_of::<String>()).sum::<usize>()
+            + result.explanation.iter()
+                .map(|e| e.clause.len() + e.terms.iter().map(|t| t.len() + size_of::<String>()).sum::<usize>()
+                    + e.matches.iter().map(|m| m.field.len() + m.term.len() + size_of_val(m)).sum::<usize>()
+                    + size_of_val(e))
+                .sum::<usize>()
+    };
+    size_of::<SearchResults>()
+        + results.results.iter().map(result_size).sum::<usize>()
+        + results.facets.iter()
+            .map(|(name, counts)| name.len() + counts.iter().map(|c| c.value.len() + size_of_val(c)).sum::<usize>())
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inverted_index::QueryResult;
+
+    use super::*;
+
+    fn results(count: usize) -> SearchResults {
+        SearchResults {
+            results: (0..count)
+                .map(|i| QueryResult { document: i.to_string(), rank: 1.0, ..Default::default() })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_query_cache() {
+        let cache = QueryCache::new(QueryCacheConfig { capacity: 2, max_results: 3, ..Default::default() });
+        let options = QueryOptions::default();
+        let key = |query| QueryCache::key(query, &options);
+        assert_eq!(key(" great  movie "), key("great movie"));
+        assert_ne!(key("great movie"), QueryCache::key("great movie", &QueryOptions { fuzzy: true, ..Default::default() }));
+
+        cache.insert(key("a"), 0, Arc::new(results(1)));
+        cache.insert(key("b"), 0, Arc::new(results(2)));
+        cache.insert(key("too many"), 0, Arc::new(results(4)));
+        assert_eq!(cache.get(&key("a"), 0).unwrap().results.len(), 1);
+        assert!(cache.get(&key("too many"), 0).is_none());
+        cache.insert(key("c"), 0, Arc::new(results(3)));
+        assert!(cache.get(&key("b"), 0).is_none(), "least recently used entry is evicted");
+        assert!(cache.get(&key("c"), 1).is_none(), "entries of older generations are dropped");
+        let stats = cache.stats();
+        assert_eq!(stats, QueryCacheStats { capacity: 2, entries: 1, hits: 1, misses: 3, ..stats.clone() });
+        assert_eq!(stats.bytes, key("a").len() + approximate_size(&results(1)));
+
+        let disabled = QueryCache::new(QueryCacheConfig { capacity: 0, max_results: 3, ..Default::default() });
+        disabled.insert(key("a"), 0, Arc::new(results(1)));
+        assert!(disabled.get(&key("a"), 0).is_none());
+    }
+
+    #[test]
+    fn test_query_cache_bytes() {
+        // keys have at most 3 digits, and each of the 2 shards has room for 3 entries
+        let entry_size = 3 + approximate_size(&results(10));
+        let cache = QueryCache::new(QueryCacheConfig { capacity: 128, max_bytes: 6 * entry_size, ..Default::default() });
+        assert_eq!(cache.shards.len(), 2);
+        for i in 0..1000 {
+            cache.insert(i.to_string(), 0, Arc::new(results(10)));
+            assert!(cache.stats().bytes <= 6 * entry_size);
+        }
+        assert!((2..=6).contains(&cache.stats().entries));
+
+        let too_big = QueryCache::new(QueryCacheConfig { capacity: 1, max_bytes: entry_size - 4, ..Default::default() });
+        too_big.insert("0".to_owned(), 0, Arc::new(results(10)));
+        assert_eq!(too_big.stats().entries, 0);
+
+        let results = Arc::new(results(1));
+        let cache = QueryCache::default();
+        cache.insert("a".to_owned(), 0, Arc::clone(&results));
+        assert!(Arc::ptr_eq(&cache.get("a", 0).unwrap(), &results), "hits share the cached results");
+    }
+}
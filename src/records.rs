@@ -0,0 +1,195 @@
+use std::{collections::{BTreeMap, HashSet}, io, path::Path};
+
+use serde_json::{Map, Value};
+
+/// Separates the path of a record file from the ID of a record in a document path, like in `reviews.jsonl#42`
+pub const RECORD_SEPARATOR: char = '#';
+
+/// Formats of files holding one document per line or row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    JsonLines,
+    /// Comma separated values with a header row naming the fields
+    Csv,
+}
+
+impl RecordFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "jsonl" | "ndjson" => Some(Self::JsonLines),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Which fields of a record make up a document
+#[derive(Debug, Clone)]
+pub struct RecordOptions {
+    /// Field holding the ID used in the document path.
+    /// Records without it, or with an empty one, are identified by the number of the line they start on
+    pub id_field: Option<String>,
+    /// Fields holding the text to index. The text of several fields is joined with new lines
+    pub text_fields: Vec<String>,
+}
+
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            id_field: None,
+            text_fields: vec!["text".to_owned()],
+        }
+    }
+}
+
+/// A single document read from a record file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub id: String,
+    pub text: String,
+    /// Every field that is neither the ID nor text
+    pub metadata: BTreeMap<String, Value>,
+    /// Size in bytes of the record in its file, without the line break ending it
+    pub size: usize,
+}
+
+/// Splits `content` into records. A record that can't be read yields an error naming the line it starts on,
+/// but doesn't stop the ones after it. So does a record with the same ID as an earlier one, which is not read
+pub fn parse_records(content: &str, format: RecordFormat, options: &RecordOptions) -> Vec<io::Result<Record>> {
+    let mut ids = HashSet::new();
+    let mut unique = |record: io::Result<Record>, line: usize| {
+        let record = record?;
+        match ids.insert(record.id.clone()) {
+            true => Ok(record),
+            false => Err(record_error(line, format!("duplicate ID {:?}", record.id))),
+        }
+    };
+    match format {
+        RecordFormat::JsonLines => content.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let fields = match serde_json::from_str::<Value>(line) {
+                    Ok(Value::Object(fields)) => fields,
+                    Ok(_) => return Err(record_error(i + 1, "not a JSON object")),
+                    Err(err) => return Err(record_error(i + 1, err)),
+                };
+                unique(to_record(fields, i + 1, line.len(), options), i + 1)
+            })
+            .collect(),
+        RecordFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .flexible(true)
+                .from_reader(content.as_bytes());
+            let headers = match reader.headers() {
+                Ok(headers) => headers.clone(),
+                Err(err) => return vec![Err(record_error(1, err))],
+            };
+            let mut records = vec![];
+            let mut row = csv::StringRecord::new();
+            loop {
+                let record = match reader.read_record(&mut row) {
+                    Ok(false) => break,
+                    // the error of a row names its line already
+                    Err(err) => Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+                    Ok(true) => {
+                        let (line, start) = row.position().map_or((0, 0), |p| (p.line() as usize, p.byte() as usize));
+                        let end = reader.position().byte() as usize;
+                        let size = content[start..end].trim_end_matches(['\r', '\n']).len();
+                        let fields = headers.iter()
+                            .zip(row.iter())
+                            .map(|(name, value)| (name.to_owned(), Value::String(value.to_owned())))
+                            .collect();
+                        unique(to_record(fields, line, size, options), line)
+                    },
+                };
+                records.push(record);
+            }
+            records
+        },
+    }
+}
+
+/// `number` is the line the record starts on, counting from 1, and `size` its length in bytes
+fn to_record(mut fields: Map<String, Value>, number: usize, size: usize, options: &RecordOptions) -> io::Result<Record> {
+    let id = options.id_field.as_ref()
+        .and_then(|field| fields.remove(field))
+        .map(|id| match id {
+            Value::String(id) => id,
+            id => id.to_string(),
+        })
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| number.to_string());
+
+    let text: Vec<String> = options.text_fields.iter()
+        .filter_map(|field| fields.remove(field))
+        .map(|text| match text {
+            Value::String(text) => text,
+            text => text.to_string(),
+        })
+        .collect();
+    if text.is_empty() {
+        return Err(record_error(number, format!("none of the text fields {:?}", options.text_fields)));
+    }
+
+    Ok(Record {
+        id,
+        text: text.join("\n"),
+        metadata: fields.into_iter().collect(),
+        size,
+    })
+}
+
+fn record_error(number: usize, err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", number, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let options = RecordOptions {
+            id_field: Some("id".to_owned()),
+            text_fields: vec!["title".to_owned(), "review".to_owned()],
+        };
+
+        let jsonl = r#"{"id": "tt123", "title": "Kaalai", "review": "Pathetic", "rating": 1}
+
+{"title": "Vallavan", "review": "No story", "tags": ["tamil"]}
+not json
+{"id": 7, "rating": 3}
+{"id": "tt123", "review": "Again"}
+{"id": 3, "review": "Taken by the record on line 3"}
+"#;
+        let records = parse_records(jsonl, RecordFormat::JsonLines, &options);
+        assert_eq!(records.len(), 6);
+        assert_eq!(records[0].as_ref().unwrap(), &Record {
+            id: "tt123".to_owned(),
+            text: "Kaalai\nPathetic".to_owned(),
+            metadata: BTreeMap::from([("rating".to_owned(), json!(1))]),
+            size: jsonl.lines().next().unwrap().len(),
+        });
+        let second = records[1].as_ref().unwrap();
+        assert_eq!((second.id.as_str(), &second.metadata["tags"]), ("3", &json!(["tamil"])));
+        assert!(records[2].as_ref().unwrap_err().to_string().starts_with("line 4:"));
+        assert!(records[3].as_ref().unwrap_err().to_string().starts_with("line 5:"));
+        assert_eq!(records[4].as_ref().unwrap_err().to_string(), "line 6: duplicate ID \"tt123\"");
+        assert_eq!(records[5].as_ref().unwrap_err().to_string(), "line 7: duplicate ID \"3\"");
+
+        let csv = "id,review,rating\ntt1,\"Great,\nreally\",9\n,Awful,1\n";
+        let records: Vec<Record> = parse_records(csv, RecordFormat::Csv, &options)
+            .into_iter()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(records[0].id, "tt1");
+        assert_eq!(records[0].text, "Great,\nreally");
+        assert_eq!(records[0].metadata, BTreeMap::from([("rating".to_owned(), json!("9"))]));
+        assert_eq!(records[1].id, "4", "records are numbered by the line they start on");
+        assert_eq!((records[0].size, records[1].size), ("tt1,\"Great,\nreally\",9".len(), ",Awful,1".len()));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{fs, io, path::Path, collections::HashSet};
+
+use serde::{Serialize, Deserialize};
+
+/// Named fields documents are made of
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schema {
+    pub fields: Vec<Field>,
+    /// Field that whole files and the text of records are inserted into
+    pub default_field: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    /// Whether the field can be searched
+    #[serde(default = "default_indexed")]
+    pub indexed: bool,
+    /// Whether the text of the field is kept and returned with query results
+    #[serde(default)]
+    pub stored: bool,
+    /// Multiplies the weight of matches in this field
+    #[serde(default = "default_boost")]
+    pub boost: f64,
+    /// Whether every document keeps its terms of the field, so similar documents are found without scanning the field
+    #[serde(default)]
+    pub term_vectors: bool,
+}
+
+fn default_indexed() -> bool {
+    true
+}
+
+fn default_boost() -> f64 {
+    1.0
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self {
+            fields: vec![Field::new("text")],
+            default_field: "text".to_owned(),
+        }
+    }
+}
+
+impl Field {
+    /// An indexed, not stored field with no boost and no term vectors
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            indexed: default_indexed(),
+            stored: false,
+            boost: default_boost(),
+            term_vectors: false,
+        }
+    }
+}
+
+impl Schema {
+    /// Reads a schema from a YAML or JSON file
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let schema: Self = serde_yaml::from_str(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// Checks that field names are unique and usable in queries, and that the default field is indexed
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        let mut names = HashSet::new();
+        for field in &self.fields {
+            if field.name.is_empty() || field.name.contains(|c: char| c == ':' || c.is_whitespace()) {
+                return invalid(format!("invalid field name `{}`", field.name));
+            }
+            if !names.insert(&field.name) {
+                return invalid(format!("field `{}` is defined more than once", field.name));
+            }
+            if !field.boost.is_finite() || field.boost < 0.0 {
+                return invalid(format!("field `{}` has boost {}, it must be a number of at least 0", field.name, field.boost));
+            }
+        }
+        match self.field(&self.default_field) {
+            Some(field) if field.indexed => Ok(()),
+            Some(_) => invalid(format!("default field `{}` is not indexed", self.default_field)),
+            None => invalid(format!("default field `{}` is not defined", self.default_field)),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    pub fn indexed_fields(&self) -> impl Iterator<Item = &Field> {
+        self.fields.iter().filter(|f| f.indexed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_config() {
+        let schema: Schema = serde_yaml::from_str("
+fields:
+  - name: title
+    boost: 2.5
+  - name: body
+    term_vectors: true
+  - name: author
+    indexed: false
+    stored: true
+default_field: body
+").unwrap();
+        assert!(schema.validate().is_ok());
+        assert_eq!(schema.indexed_fields().map(|f| f.name.as_str()).collect::<Vec<_>>(), vec!["title", "body"]);
+        assert_eq!(schema.field("title").unwrap().boost, 2.5);
+        assert!(schema.field("author").unwrap().stored);
+        assert!(schema.field("body").unwrap().term_vectors && !schema.field("title").unwrap().term_vectors);
+
+        let invalid_schemas = [
+            "fields: [{name: text}, {name: text}]",
+            "fields: [{name: 'a:b'}]\ndefault_field: 'a:b'",
+            "fields: [{name: title}]",
+            "fields: [{name: text, indexed: false}]",
+            "fields: [{name: text, boost: -1}]",
+            "fields: [{name: text, boost: .nan}]",
+            "fields: [{name: text, boost: .inf}]",
+        ];
+        for yaml in invalid_schemas {
+            let schema: Schema = serde_yaml::from_str(yaml).unwrap();
+            assert!(schema.validate().is_err(), "schema {}", yaml);
+        }
+    }
+}
@@ -2,16 +2,27 @@ use std::{net::{ToSocketAddrs, TcpListener, TcpStream}, io, time::Duration, sync
 
 use log::{error};
 
-use crate::{inverted_index::InvertedIndex, messages::{Request, Response, FromMessage, IntoMessage}};
+use crate::{inverted_index::{QueryOptions, SearchResults}, frozen_index::FrozenIndex, messages::{Request, Response, FromMessage, IntoMessage, QueryResponse}, query_cache::{QueryCache, QueryCacheConfig}};
 
 pub struct Server {
-    inverted_index: Arc<InvertedIndex>,
+    inverted_index: Arc<FrozenIndex>,
+    query_cache: Arc<QueryCache>,
     thread_pool: ThreadPool,
 }
 
 impl Server {
-    pub fn new(inverted_index: Arc<InvertedIndex>, thread_count: usize) -> Self {
-        Self { inverted_index, thread_pool: ThreadPool::new(thread_count) }
+    pub fn new(inverted_index: Arc<FrozenIndex>, thread_count: usize) -> Self {
+        Self {
+            inverted_index,
+            query_cache: Arc::new(QueryCache::default()),
+            thread_pool: ThreadPool::new(thread_count),
+        }
+    }
+
+    /// Caches the results of recent queries. The index never changes, so entries are only ever evicted
+    pub fn with_query_cache(mut self, config: QueryCacheConfig) -> Self {
+        self.query_cache = Arc::new(QueryCache::new(config));
+        self
     }
 
     pub fn listen(&mut self, addr: impl ToSocketAddrs) -> io::Result<()> {
@@ -20,8 +31,9 @@ impl Server {
             match stream {
                 Ok(mut x) => {
                     let inverted_index = Arc::clone(&self.inverted_index);
+                    let query_cache = Arc::clone(&self.query_cache);
                     self.thread_pool.run_job(move ||{
-                        if let Err(err) = Self::handle_stream(&mut x, inverted_index) {
+                        if let Err(err) = Self::handle_stream(&mut x, &inverted_index, &query_cache) {
                             error!("Connection to {:?} ended with an error: {}", 
                                 x.peer_addr(), err);
                         }
@@ -36,7 +48,7 @@ impl Server {
         Ok(())
     }
 
-    fn handle_stream(stream: &mut TcpStream, inverted_index: Arc<InvertedIndex>) -> io::Result<()> {
+    fn handle_stream(stream: &mut TcpStream, inverted_index: &FrozenIndex, query_cache: &QueryCache) -> io::Result<()> {
         stream.set_read_timeout(Some(Duration::from_secs(10)))?;
         stream.set_write_timeout(Some(Duration::from_secs(10)))?;
 
@@ -44,7 +56,22 @@ impl Server {
         let response = match request {
             Request::Ping => Response::Pong,
             Request::Query(s) => Response::QueryResult(
-                inverted_index.query(&s)),
+                Self::run_query(inverted_index, query_cache, &s, &QueryOptions::default())),
+            Request::QueryWithOptions(r) => Response::QueryResult(
+                Self::run_query(inverted_index, query_cache, &r.query, &r.options)),
+            Request::MoreLikeThis(r) => match inverted_index.more_like_this(&r.document, r.max_terms, r.max_results) {
+                Some(results) => Response::QueryResult(QueryResponse {
+                    search: Arc::new(SearchResults { results, ..Default::default() }),
+                    ..Default::default()
+                }),
+                None => Response::Error("document is not in the index".to_owned()),
+            },
+            Request::Stats(r) => {
+                let mut stats = inverted_index.stats(r.top);
+                stats.query_cache = query_cache.stats();
+                Response::Stats(stats)
+            },
+            Request::Term(s) => Response::TermInfo(inverted_index.term_info(&s)),
             Request::QueryFile(s) => {
                 match Response::from_file_path(&s) {
                     Ok(r) => r,
@@ -58,6 +85,20 @@ impl Server {
         };
         response.write(stream)
     }
+
+    fn run_query(inverted_index: &FrozenIndex, query_cache: &QueryCache, query: &str, options: &QueryOptions) -> QueryResponse {
+        let key = QueryCache::key(query, options);
+        let search = query_cache.get(&key, 0).unwrap_or_else(|| {
+            let search = Arc::new(inverted_index.search(query, options));
+            query_cache.insert(key, 0, Arc::clone(&search));
+            search
+        });
+        let suggestions = match search.results.is_empty() {
+            true => inverted_index.suggest(query, options.max_suggestions),
+            false => vec![],
+        };
+        QueryResponse { search, suggestions }
+    }
 }
 
 type Job = Box<dyn FnOnce() + Send + 'static>;
@@ -108,11 +149,8 @@ struct Worker {
 impl Worker {
     fn new(receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Self {
         let thread = thread::spawn(move || {
-            loop {
-                match receiver.lock().unwrap().recv() {
-                    Ok(job) => job(),
-                    Err(_) => break,
-                }
+            while let Ok(job) = receiver.lock().unwrap().recv() {
+                job();
             }
         });
         Self { thread: Some(thread) }
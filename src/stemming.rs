@@ -0,0 +1,108 @@
+use std::collections::HashSet;
+
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::{Serialize, Deserialize};
+use whatlang::Lang;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StemmerLanguage {
+    /// The original English Porter stemmer
+    #[default]
+    Porter,
+    Arabic,
+    Danish,
+    Dutch,
+    English,
+    Finnish,
+    French,
+    German,
+    Greek,
+    Hungarian,
+    Italian,
+    Norwegian,
+    Portuguese,
+    Romanian,
+    Russian,
+    Spanish,
+    Swedish,
+    Tamil,
+    Turkish,
+    /// Leaves tokens as they are
+    None,
+    /// Detects the language of each document separately
+    Auto,
+}
+
+impl StemmerLanguage {
+    /// Stems `token`. `Auto` has to be resolved into a concrete language beforehand,
+    /// otherwise `token` is returned unchanged
+    pub fn stem(self, token: String) -> String {
+        match self {
+            Self::Porter => porter_stemmer::stem(&token),
+            Self::None | Self::Auto => token,
+            language => {
+                let algorithm = language.algorithm().unwrap();
+                Stemmer::create(algorithm).stem(&token).into_owned()
+            },
+        }
+    }
+
+    /// Detects the language of a document made of `tokens`.
+    /// Returns `None` if the language is not recognized reliably or has no stemmer
+    pub fn detect(tokens: &HashSet<String>) -> Option<Self> {
+        let mut tokens: Vec<&str> = tokens.iter().map(|t| t.as_str()).collect();
+        tokens.sort();
+        let info = whatlang::detect(&tokens.join(" "))?;
+        if !info.is_reliable() {
+            return None;
+        }
+        let language = match info.lang() {
+            Lang::Ara => Self::Arabic,
+            Lang::Dan => Self::Danish,
+            Lang::Nld => Self::Dutch,
+            Lang::Eng => Self::English,
+            Lang::Fin => Self::Finnish,
+            Lang::Fra => Self::French,
+            Lang::Deu => Self::German,
+            Lang::Ell => Self::Greek,
+            Lang::Hun => Self::Hungarian,
+            Lang::Ita => Self::Italian,
+            Lang::Nob => Self::Norwegian,
+            Lang::Por => Self::Portuguese,
+            Lang::Ron => Self::Romanian,
+            Lang::Rus => Self::Russian,
+            Lang::Spa => Self::Spanish,
+            Lang::Swe => Self::Swedish,
+            Lang::Tam => Self::Tamil,
+            Lang::Tur => Self::Turkish,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    fn algorithm(self) -> Option<Algorithm> {
+        let algorithm = match self {
+            Self::Arabic => Algorithm::Arabic,
+            Self::Danish => Algorithm::Danish,
+            Self::Dutch => Algorithm::Dutch,
+            Self::English => Algorithm::English,
+            Self::Finnish => Algorithm::Finnish,
+            Self::French => Algorithm::French,
+            Self::German => Algorithm::German,
+            Self::Greek => Algorithm::Greek,
+            Self::Hungarian => Algorithm::Hungarian,
+            Self::Italian => Algorithm::Italian,
+            Self::Norwegian => Algorithm::Norwegian,
+            Self::Portuguese => Algorithm::Portuguese,
+            Self::Romanian => Algorithm::Romanian,
+            Self::Russian => Algorithm::Russian,
+            Self::Spanish => Algorithm::Spanish,
+            Self::Swedish => Algorithm::Swedish,
+            Self::Tamil => Algorithm::Tamil,
+            Self::Turkish => Algorithm::Turkish,
+            Self::Porter | Self::None | Self::Auto => return None,
+        };
+        Some(algorithm)
+    }
+}
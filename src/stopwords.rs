@@ -0,0 +1,82 @@
+use serde::{Serialize, Deserialize};
+
+/// Languages with a built-in stopword list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopwordLanguage {
+    English,
+    French,
+    German,
+    Spanish,
+    Italian,
+    Portuguese,
+}
+
+impl StopwordLanguage {
+    /// Lowercase stopwords of the language
+    pub fn stopwords(self) -> &'static [&'static str] {
+        match self {
+            Self::English => ENGLISH,
+            Self::French => FRENCH,
+            Self::German => GERMAN,
+            Self::Spanish => SPANISH,
+            Self::Italian => ITALIAN,
+            Self::Portuguese => PORTUGUESE,
+        }
+    }
+}
+
+const ENGLISH: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "am", "an", "and", "any", "are",
+    "as", "at", "be", "because", "been", "before", "being", "below", "between", "both", "but",
+    "by", "can", "could", "did", "do", "does", "doing", "down", "during", "each", "few", "for",
+    "from", "further", "had", "has", "have", "having", "he", "her", "here", "hers", "herself",
+    "him", "himself", "his", "how", "i", "if", "in", "into", "is", "it", "it's", "its", "itself",
+    "just", "me", "more", "most", "my", "myself", "no", "nor", "not", "now", "of", "off", "on",
+    "once", "only", "or", "other", "our", "ours", "ourselves", "out", "over", "own", "same",
+    "she", "should", "so", "some", "such", "than", "that", "the", "their", "theirs", "them",
+    "themselves", "then", "there", "these", "they", "this", "those", "through", "to", "too",
+    "under", "until", "up", "very", "was", "we", "were", "what", "when", "where", "which",
+    "while", "who", "whom", "why", "will", "with", "would", "you", "your", "yours", "yourself",
+    "yourselves",
+];
+
+/// Elided words like the `l` of `l'histoire` are listed without their apostrophe,
+/// the way the tokenizer leaves them when it splits contractions
+const FRENCH: &[&str] = &[
+    "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "et", "eux", "il",
+    "ils", "je", "la", "le", "les", "leur", "lui", "ma", "mais", "me", "même", "mes", "moi", "mon",
+    "ne", "nos", "notre", "nous", "on", "ou", "par", "pas", "pour", "qu", "que", "qui", "sa", "se",
+    "ses", "son", "sur", "ta", "te", "tes", "toi", "ton", "tu", "un", "une", "vos", "votre",
+    "vous", "c", "d", "j", "l", "m", "n", "s", "t", "y", "été", "est", "sont", "était",
+];
+
+const GERMAN: &[&str] = &[
+    "aber", "alle", "als", "also", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "da",
+    "damit", "dann", "das", "dass", "dem", "den", "der", "des", "die", "dies", "diese", "doch",
+    "du", "durch", "ein", "eine", "einem", "einen", "einer", "es", "für", "hat", "ich", "ihr",
+    "im", "in", "ist", "ja", "kein", "man", "mit", "nach", "nicht", "noch", "nur", "oder",
+    "sich", "sie", "sind", "so", "über", "um", "und", "uns", "von", "vor", "war", "was", "wenn",
+    "wie", "wir", "wird", "zu", "zum", "zur",
+];
+
+const SPANISH: &[&str] = &[
+    "a", "al", "algo", "como", "con", "de", "del", "el", "ella", "en", "es", "esta", "este",
+    "fue", "ha", "hay", "la", "las", "le", "les", "lo", "los", "más", "me", "mi", "muy", "no",
+    "nos", "o", "para", "pero", "por", "que", "se", "si", "sin", "sobre", "son", "su", "sus",
+    "también", "te", "tu", "un", "una", "uno", "y", "ya", "yo",
+];
+
+const ITALIAN: &[&str] = &[
+    "a", "ad", "al", "alla", "anche", "che", "chi", "ci", "come", "con", "da", "dal", "degli",
+    "dei", "del", "della", "di", "e", "è", "gli", "ha", "i", "il", "in", "io", "la", "le", "lo",
+    "ma", "mi", "ne", "nel", "nella", "non", "o", "per", "più", "se", "si", "sono", "su", "sua",
+    "suo", "ti", "tra", "tu", "un", "una", "uno",
+];
+
+const PORTUGUESE: &[&str] = &[
+    "a", "ao", "as", "com", "como", "da", "das", "de", "do", "dos", "e", "é", "ela", "ele", "em",
+    "entre", "era", "eu", "foi", "há", "isso", "já", "lhe", "mais", "mas", "me", "mesmo", "meu",
+    "muito", "na", "não", "nas", "no", "nos", "o", "os", "ou", "para", "pela", "pelo", "por",
+    "quando", "que", "se", "sem", "ser", "seu", "sua", "também", "um", "uma", "você",
+];
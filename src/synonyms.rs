@@ -0,0 +1,162 @@
+use std::{fs, io};
+
+use serde::{Serialize, Deserialize};
+
+/// Words and phrases query words are expanded into.
+///
+/// Rules are written one per line. `film, movie, motion picture` is a group of equivalent phrases,
+/// each expanding into the others, while `flick, feature => film` expands `flick` and `feature` into `film` only
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "SynonymsConfig", into = "SynonymsConfig")]
+pub struct Synonyms {
+    config: SynonymsConfig,
+    rules: Vec<SynonymRule>,
+}
+
+/// A phrase and the phrases it expands into, each made of one or more words
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SynonymRule {
+    pub phrase: Vec<String>,
+    pub synonyms: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SynonymsConfig {
+    pub rules: Vec<String>,
+    /// Files with one rule per line. Empty lines and lines starting with `#` are ignored
+    pub files: Vec<String>,
+    /// Rank of a synonym match relative to a match of the query words themselves
+    pub weight: f64,
+}
+
+impl Default for SynonymsConfig {
+    fn default() -> Self {
+        Self {
+            rules: vec![],
+            files: vec![],
+            weight: 0.5,
+        }
+    }
+}
+
+impl Synonyms {
+    pub fn rules(&self) -> &[SynonymRule] {
+        &self.rules
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.config.weight
+    }
+}
+
+/// Parses a single rule into one `SynonymRule` per phrase it expands
+fn parse_rule(rule: &str) -> Result<Vec<SynonymRule>, String> {
+    let phrases = |s: &str| -> Vec<Vec<String>> {
+        s.split(',')
+            .map(|phrase| phrase.split_whitespace().map(str::to_owned).collect::<Vec<_>>())
+            .filter(|phrase| !phrase.is_empty())
+            .collect()
+    };
+
+    match rule.split_once("=>") {
+        Some((from, to)) => {
+            let (from, to) = (phrases(from), phrases(to));
+            if from.is_empty() || to.is_empty() {
+                return Err(format!("synonym rule `{}` needs phrases on both sides of `=>`", rule));
+            }
+            Ok(from.into_iter()
+                .map(|phrase| SynonymRule { phrase, synonyms: to.clone() })
+                .collect())
+        },
+        None => {
+            let group = phrases(rule);
+            if group.len() < 2 {
+                return Err(format!("synonym rule `{}` needs at least two phrases", rule));
+            }
+            Ok(group.iter()
+                .map(|phrase| SynonymRule {
+                    phrase: phrase.clone(),
+                    synonyms: group.iter().filter(|other| *other != phrase).cloned().collect(),
+                })
+                .collect())
+        },
+    }
+}
+
+impl TryFrom<SynonymsConfig> for Synonyms {
+    type Error = io::Error;
+
+    fn try_from(config: SynonymsConfig) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut lines = config.rules.clone();
+        for file in &config.files {
+            let content = fs::read_to_string(file).map_err(|err|
+                io::Error::new(err.kind(), format!("error reading synonyms from {}: {}", file, err)))?;
+            lines.extend(content.lines()
+                .map(|l| l.trim())
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(|l| l.to_owned()));
+        }
+
+        let mut rules = vec![];
+        for line in &lines {
+            rules.extend(parse_rule(line).map_err(invalid)?);
+        }
+        if !config.weight.is_finite() || config.weight < 0.0 {
+            return Err(invalid(format!("synonym weight {} has to be a number of at least 0", config.weight)));
+        }
+        Ok(Self { config, rules })
+    }
+}
+
+impl From<Synonyms> for SynonymsConfig {
+    fn from(synonyms: Synonyms) -> Self {
+        synonyms.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrases(phrases: &[&str]) -> Vec<Vec<String>> {
+        phrases.iter()
+            .map(|phrase| phrase.split(' ').map(str::to_owned).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_synonyms_config() {
+        let path = std::env::temp_dir().join(format!("parallel_computing_test_synonyms_{}.txt", std::process::id()));
+        fs::write(&path, "# movies\nflick, feature => film\n\n").unwrap();
+        let synonyms: Synonyms = serde_yaml::from_str(&format!("
+rules: ['film, movie,  motion picture']
+files: [{:?}]
+weight: 0.25
+", path)).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(synonyms.weight(), 0.25);
+        assert_eq!(synonyms.rules(), vec![
+            SynonymRule { phrase: phrases(&["film"])[0].clone(), synonyms: phrases(&["movie", "motion picture"]) },
+            SynonymRule { phrase: phrases(&["movie"])[0].clone(), synonyms: phrases(&["film", "motion picture"]) },
+            SynonymRule { phrase: phrases(&["motion picture"])[0].clone(), synonyms: phrases(&["film", "movie"]) },
+            SynonymRule { phrase: phrases(&["flick"])[0].clone(), synonyms: phrases(&["film"]) },
+            SynonymRule { phrase: phrases(&["feature"])[0].clone(), synonyms: phrases(&["film"]) },
+        ]);
+
+        let invalid_configs = [
+            "rules: [film]",
+            "rules: ['film =>']",
+            "rules: [', => film']",
+            "weight: -1",
+            "weight: .inf",
+            "weight: .nan",
+            "files: [/nonexistent/synonyms.txt]",
+        ];
+        for yaml in invalid_configs {
+            assert!(serde_yaml::from_str::<Synonyms>(yaml).is_err(), "config {}", yaml);
+        }
+    }
+}
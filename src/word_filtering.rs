@@ -1,10 +1,18 @@
 use std::{io::{self, Read}, collections::HashSet};
 
-pub fn reader_to_words<'a>(mut reader: impl Read) -> io::Result<HashSet<String>> {
+use serde::{Serialize, Deserialize};
+use unicode_normalization::char::is_combining_mark;
+
+pub fn reader_to_words(reader: impl Read) -> io::Result<HashSet<String>> {
+    reader_to_words_with(reader, is_word_char)
+}
+
+/// Same as `reader_to_words`, but words are runs of characters for which `is_word_char` returns true
+pub fn reader_to_words_with(mut reader: impl Read, is_word_char: impl Fn(char) -> bool + Copy) -> io::Result<HashSet<String>> {
     let mut words_set = HashSet::new();
     let mut word_left: Option<String> = None;
 
-    let mut buffer =  [0 as u8; 264];
+    let mut buffer =  [0_u8; 264];
     let mut read_start = 0;
 
     loop {
@@ -16,9 +24,10 @@ pub fn reader_to_words<'a>(mut reader: impl Read) -> io::Result<HashSet<String>>
             break;
         }
 
-        let (string_read, bytes_left) = bytes_to_str(&buffer[..bytes_read])?;
+        let content_len = read_start + bytes_read;
+        let (string_read, bytes_left) = bytes_to_str(&buffer[..content_len])?;
 
-        match scan_for_words_from_reader(string_read) {
+        match scan_for_words_from_reader(string_read, is_word_char) {
             ScanForWordsResult::Words(WordsWithAlphanumericRuns{
                 mut words, leading_run, trailing_run}) => 
             {
@@ -51,7 +60,6 @@ pub fn reader_to_words<'a>(mut reader: impl Read) -> io::Result<HashSet<String>>
         read_start = match bytes_left {
             None => 0,
             Some(bytes_left) => {
-                let content_len = read_start + bytes_read;
                 buffer.copy_within((content_len-bytes_left)..content_len, 0);
                 bytes_left
             },
@@ -62,7 +70,7 @@ pub fn reader_to_words<'a>(mut reader: impl Read) -> io::Result<HashSet<String>>
         words_set.insert(word);
     }
 
-    return Ok(words_set);
+    Ok(words_set)
 }
 
 #[derive(Debug)]
@@ -81,6 +89,11 @@ struct WordsWithAlphanumericRuns<'a> {
 
 /// Scans `s` for runs of alphanumeric characters and returns them in `HashSet<String>`
 pub fn scan_for_unique_words(s: &str) -> Option<HashSet<String>> {
+    scan_for_unique_words_with(s, is_word_char)
+}
+
+/// Same as `scan_for_unique_words`, but words are runs of characters for which `is_word_char` returns true
+pub fn scan_for_unique_words_with(s: &str, is_word_char: impl Fn(char) -> bool) -> Option<HashSet<String>> {
     if s.is_empty() {
         return None;
     }
@@ -130,7 +143,7 @@ pub fn scan_for_unique_words(s: &str) -> Option<HashSet<String>> {
 /// If `s` is of the form of `[N](AN){m}A`, `Words` is returned with trailing_run = Some(A)
 /// 
 /// If `s` is of the form of `A`, `SingleAlphanumericRun` is returned.
-fn scan_for_words_from_reader<'a, 'b>(s: &'a str) -> ScanForWordsResult {
+fn scan_for_words_from_reader(s: &str, is_word_char: impl Fn(char) -> bool) -> ScanForWordsResult<'_> {
     if s.is_empty() {
         return ScanForWordsResult::NoWords
     }
@@ -154,12 +167,10 @@ fn scan_for_words_from_reader<'a, 'b>(s: &'a str) -> ScanForWordsResult {
         }
     }
 
-    match words.is_empty() {
-        true => match word_start {
-            Some(_) => ScanForWordsResult::SingleAlphanumericRun,
-            None => ScanForWordsResult::NoWords,
-        },
-        false => ScanForWordsResult::Words(WordsWithAlphanumericRuns{ 
+    match (words.is_empty(), word_start) {
+        (true, None) => ScanForWordsResult::NoWords,
+        (true, Some(0)) => ScanForWordsResult::SingleAlphanumericRun,
+        _ => ScanForWordsResult::Words(WordsWithAlphanumericRuns{ 
                 words, leading_run, trailing_run: match word_start {
                 Some(i) => Some(&s[i..]),
                 _ => None,
@@ -168,8 +179,144 @@ fn scan_for_words_from_reader<'a, 'b>(s: &'a str) -> ScanForWordsResult {
     }
 }
 
-fn is_word_char(c: char) -> bool {
-    c.is_alphanumeric() || c == '\''
+/// Combining marks count as word characters so that decomposed letters like `e\u{301}` stay in one word
+pub fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '\'' || is_combining_mark(c)
+}
+
+/// Whether `c` belongs to a script that is written without spaces between words:
+/// Han, Hiragana, Katakana or Hangul
+pub fn is_cjk_char(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}'     // Hangul Jamo
+        | '\u{3040}'..='\u{30FF}'   // Hiragana, Katakana
+        | '\u{3130}'..='\u{318F}'   // Hangul Compatibility Jamo
+        | '\u{31F0}'..='\u{31FF}'   // Katakana Phonetic Extensions
+        | '\u{3400}'..='\u{4DBF}'   // CJK Unified Ideographs Extension A
+        | '\u{4E00}'..='\u{9FFF}'   // CJK Unified Ideographs
+        | '\u{AC00}'..='\u{D7AF}'   // Hangul Syllables
+        | '\u{F900}'..='\u{FAFF}'   // CJK Compatibility Ideographs
+        | '\u{FF66}'..='\u{FF9F}'   // Halfwidth Katakana
+        | '\u{20000}'..='\u{2FA1F}' // CJK Unified Ideographs Extensions B-F, Compatibility Supplement
+    )
+}
+
+/// Splits `word` into runs of CJK and non-CJK characters,
+/// turning every CJK run into overlapping character bigrams.
+///
+/// Non-CJK runs are kept as they are, a CJK run of a single character is kept as a unigram.
+/// `"東京タワーへ行くtrip"` becomes `["東京", "京タ", "タワ", "ワー", "ーへ", "へ行", "行く", "trip"]`
+pub fn cjk_bigrams(word: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut run: Vec<char> = vec![];
+    let mut run_is_cjk = false;
+
+    let mut flush = |run: &mut Vec<char>, is_cjk: bool| {
+        match (is_cjk, run.len()) {
+            (_, 0) => {},
+            (true, 1) | (false, _) => tokens.push(run.iter().collect()),
+            (true, _) => tokens.extend(run.windows(2).map(|w| w.iter().collect::<String>())),
+        }
+        run.clear();
+    };
+
+    for c in word.chars() {
+        let is_cjk = is_cjk_char(c);
+        if is_cjk != run_is_cjk {
+            flush(&mut run, run_is_cjk);
+            run_is_cjk = is_cjk;
+        }
+        run.push(c);
+    }
+    flush(&mut run, run_is_cjk);
+    tokens
+}
+
+/// Kinds of tokens that are kept whole instead of being split on punctuation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StructuredToken {
+    /// `http://imdb.com/title/tt123`, `www.imdb.com`
+    Url,
+    /// `user@example.com`
+    Email,
+    /// `#bollywood`
+    Hashtag,
+    /// `@simbu`
+    Mention,
+    /// `3.14`, `1,000`, `1/10`
+    Number,
+    /// `v1.2`, `1.2.3`
+    Version,
+}
+
+/// Recognizes a whitespace delimited `chunk` as one of the structured tokens.
+///
+/// Punctuation around the token, like a trailing comma or enclosing parentheses, is not part of it.
+/// Returns the kind of the token and the token itself
+pub fn recognize_structured_token(chunk: &str) -> Option<(StructuredToken, &str)> {
+    let token = chunk
+        .trim_start_matches(['(', '[', '{', '<', '"', '\''])
+        .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}', '>', '"', '\'']);
+    if token.is_empty() {
+        return None;
+    }
+
+    let kind = if is_url(token) {
+        StructuredToken::Url
+    } else if is_email(token) {
+        StructuredToken::Email
+    } else if token.strip_prefix('#').is_some_and(is_tag_name) {
+        StructuredToken::Hashtag
+    } else if token.strip_prefix('@').is_some_and(is_tag_name) {
+        StructuredToken::Mention
+    } else if is_version(token) {
+        StructuredToken::Version
+    } else if is_number(token) {
+        StructuredToken::Number
+    } else {
+        return None;
+    };
+    Some((kind, token))
+}
+
+fn is_url(token: &str) -> bool {
+    ["http://", "https://", "ftp://", "www."].iter()
+        .filter_map(|scheme| token.strip_prefix(scheme))
+        .any(|rest| rest.starts_with(|c: char| c.is_alphanumeric()))
+}
+
+fn is_email(token: &str) -> bool {
+    let Some((local, domain)) = token.split_once('@') else {
+        return false;
+    };
+    let labels: Vec<&str> = domain.split('.').collect();
+    !local.is_empty()
+        && local.chars().all(|c| c.is_alphanumeric() || "._%+-".contains(c))
+        && labels.len() >= 2
+        && labels.iter().all(|l| !l.is_empty() && l.chars().all(|c| c.is_alphanumeric() || c == '-'))
+}
+
+fn is_tag_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Digits separated by single `.`, `,` or `/`
+fn is_number(token: &str) -> bool {
+    token.split(['.', ',', '/'])
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Dot separated digits with an optional `v` prefix. Without the prefix at least three
+/// components are required, since `1.5` is a decimal
+fn is_version(token: &str) -> bool {
+    let (components, min_count) = match token.strip_prefix(['v', 'V']) {
+        Some(rest) => (rest, 2),
+        None => (token, 3),
+    };
+    let components: Vec<&str> = components.split('.').collect();
+    components.len() >= min_count
+        && components.iter().all(|c| !c.is_empty() && c.chars().all(|c| c.is_ascii_digit()))
 }
 
 /// In-place converts a UTF8 formatted string into `&str`.
@@ -182,7 +329,7 @@ fn bytes_to_str(bytes: &[u8]) -> io::Result<(&str, Option<usize>)> {
     match std::str::from_utf8(bytes) {
         Ok(s) => Ok((s, None)),
         Err(utf8_error) => {
-            if let Some(_) = utf8_error.error_len() {
+            if utf8_error.error_len().is_some() {
                 Err(io::Error::new(io::ErrorKind::InvalidInput, 
                     "string contains invalid UTF8"))
             } else {
@@ -239,22 +386,22 @@ mod tests {
         }
     }
 
+    impl PartialEq for ScanForWordsResult<'_> {
+        fn eq(&self, other: &Self) -> bool {
+            match (self, other) {
+                (Self::Words(l), Self::Words(r)) => 
+                    l.leading_run == r.leading_run
+                    && l.trailing_run == r.trailing_run
+                    && vec_compare(&l.words, &r.words),
+                _ => core::mem::discriminant(self) == core::mem::discriminant(other),
+            }
+        }
+    }
+
     #[test]
     fn test_scan_words() {
         use ScanForWordsResult::*;
 
-        impl PartialEq for ScanForWordsResult<'_> {
-            fn eq(&self, other: &Self) -> bool {
-                match (self, other) {
-                    (Self::Words(l), Self::Words(r)) => 
-                        l.leading_run == r.leading_run
-                        && l.trailing_run == r.trailing_run
-                        && vec_compare(&l.words, &r.words),
-                    _ => core::mem::discriminant(self) == core::mem::discriminant(other),
-                }
-            }
-        }
-
         struct ScanForWordsTestCase<'a> {
             string: &'a str,
             expected_result: ScanForWordsResult<'a>,
@@ -293,6 +440,14 @@ mod tests {
                 string: "!,==<>",
                 expected_result: NoWords,
             },
+            ScanForWordsTestCase {
+                string: "  ワクワク",
+                expected_result: Words(WordsWithAlphanumericRuns { 
+                    words: vec![], 
+                    leading_run: false, 
+                    trailing_run: Some("ワクワク"),
+                })
+            },
             ScanForWordsTestCase {
                 string: "let's play!",
                 expected_result: Words(WordsWithAlphanumericRuns { 
@@ -304,7 +459,7 @@ mod tests {
         ];
 
         for case in test_cases {
-            let res = scan_for_words_from_reader(&case.string);
+            let res = scan_for_words_from_reader(case.string, is_word_char);
 
             assert_eq!(res, case.expected_result, "case `{}`:
             expected: {:?}
@@ -312,7 +467,64 @@ mod tests {
         }
     }
 
-    fn vec_compare<T>(v1: &Vec<T>, v2: &Vec<T>) -> bool
+    #[test]
+    fn test_reader_to_words_combining_marks() {
+        // `reader_to_words` reads 264 bytes at a time, so these paddings put the boundary
+        // before, inside and right after the two-byte combining acute accent
+        for padding in 255..=265 {
+            let content = format!("{}cafe\u{301} caf\u{e9}", " ".repeat(padding));
+            let words = reader_to_words(StringReader::new(&content)).unwrap();
+            let expected: HashSet<String> = vec_to_owned(vec!["cafe\u{301}", "caf\u{e9}"]).into_iter().collect();
+            assert_eq!(words, expected, "padding {}", padding);
+        }
+    }
+
+    #[test]
+    fn test_cjk_bigrams() {
+        let test_cases = vec![
+            ("アニャ", vec!["アニ", "ニャ"]),
+            ("ワクワク", vec!["ワク", "クワ", "ワク"]),
+            ("東京タワーへ行くtrip", vec!["東京", "京タ", "タワ", "ワー", "ーへ", "へ行", "行く", "trip"]),
+            ("peanuts", vec!["peanuts"]),
+            ("x日y", vec!["x", "日", "y"]),
+            ("한국어", vec!["한국", "국어"]),
+            ("", vec![]),
+        ];
+
+        for (word, expected) in test_cases {
+            assert_eq!(cjk_bigrams(word), vec_to_owned(expected), "word `{}`", word);
+        }
+    }
+
+    #[test]
+    fn test_recognize_structured_token() {
+        use StructuredToken::*;
+
+        let test_cases = vec![
+            ("http://imdb.com/title/tt123", Some((Url, "http://imdb.com/title/tt123"))),
+            ("(https://imdb.com/find?q=simbu),", Some((Url, "https://imdb.com/find?q=simbu"))),
+            ("www.imdb.com.", Some((Url, "www.imdb.com"))),
+            ("user@example.com", Some((Email, "user@example.com"))),
+            ("user@localhost", None),
+            ("#bollywood!", Some((Hashtag, "#bollywood"))),
+            ("@simbu:", Some((Mention, "@simbu"))),
+            ("1/10", Some((Number, "1/10"))),
+            ("3.14", Some((Number, "3.14"))),
+            ("1,000.", Some((Number, "1,000"))),
+            ("v1.2", Some((Version, "v1.2"))),
+            ("1.2.3", Some((Version, "1.2.3"))),
+            ("1..10", None),
+            ("life-time", None),
+            ("#", None),
+            ("...", None),
+        ];
+
+        for (chunk, expected) in test_cases {
+            assert_eq!(recognize_structured_token(chunk), expected, "chunk `{}`", chunk);
+        }
+    }
+
+    fn vec_compare<T>(v1: &[T], v2: &[T]) -> bool
     where
         T: std::cmp::PartialEq 
     {